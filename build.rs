@@ -0,0 +1,36 @@
+use std::{env, fs, path::Path};
+
+// The exact version a dependency is pinned to with an `=` requirement in Cargo.toml
+fn pinned_version(manifest: &str, name: &str) -> Option<String> {
+    let line = manifest.lines().find(|line| {
+        line.trim_start()
+            .strip_prefix(name)
+            .is_some_and(|rest| rest.trim_start().starts_with('='))
+    })?;
+    let (_, requirement) = line.split_once("\"=")?;
+    let (version, _) = requirement.split_once('"')?;
+    Some(version.trim().to_string())
+}
+
+fn main() {
+    // Cargo.toml ships with the crate, unlike Cargo.lock, so this also works as a dependency
+    let manifest_path = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest_path.display());
+    let manifest = fs::read_to_string(&manifest_path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", manifest_path.display(), e));
+
+    // Expose the versions of the libraries that produce the artifacts to the manifest
+    for (name, var) in [
+        ("proof-of-sql", "PROOF_OF_SQL_VERSION"),
+        ("blitzar", "BLITZAR_VERSION"),
+    ] {
+        let version = pinned_version(&manifest, name).unwrap_or_else(|| {
+            panic!(
+                "{} must be pinned to an exact version such as \"=1.2.3\" in Cargo.toml, so \
+                 the archive manifests can record it",
+                name
+            )
+        });
+        println!("cargo:rustc-env={}={}", var, version);
+    }
+}
@@ -0,0 +1,658 @@
+use crate::{
+    gzip::ParallelGzEncoder,
+    hex,
+    manifest::ManifestEntry,
+    progress::{CountingWriter, Phase, Progress},
+};
+use flate2::read::GzDecoder;
+use ring::digest::{Context, SHA256};
+use std::{
+    env,
+    ffi::CString,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    num::NonZeroUsize,
+    os::{
+        fd::AsRawFd,
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    },
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+};
+use tar::{Archive, Builder, EntryType, Header};
+use xz2::{
+    read::XzDecoder,
+    stream::{Check, MtStreamBuilder},
+    write::XzEncoder,
+};
+
+/// File name of the serialized public parameters.
+pub const PUBLIC_PARAMETERS_FILE: &str = "public_parameters.bin";
+/// File name of the serialized blitzar handle.
+pub const BLITZAR_HANDLE_FILE: &str = "blitzar_handle.bin";
+/// File name of the serialized verifier setup.
+pub const VERIFIER_SETUP_FILE: &str = "verifier_setup.bin";
+
+/// Environment variable holding the modification time, in seconds since the Unix epoch, to
+/// record for archive members instead of zero.
+pub const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+// Permissions recorded for every member
+const MEMBER_MODE: u32 = 0o644;
+
+/// How an archive is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+    /// A plain tar archive
+    Tar,
+    /// Gzip, compressed block-parallel
+    TarGz,
+    /// Zstandard, using zstd's own worker threads
+    TarZst,
+    /// XZ, using liblzma's multithreaded encoder
+    TarXz,
+}
+
+impl ArchiveFormat {
+    /// Every format, in increasing order of typical compression cost.
+    pub const ALL: [ArchiveFormat; 4] = [
+        ArchiveFormat::Tar,
+        ArchiveFormat::TarGz,
+        ArchiveFormat::TarZst,
+        ArchiveFormat::TarXz,
+    ];
+
+    /// The file name extension, including the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => ".tar",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::TarZst => ".tar.zst",
+            ArchiveFormat::TarXz => ".tar.xz",
+        }
+    }
+
+    /// The prover archive name used when none is given, e.g. `dory-params.tar.gz`.
+    pub fn default_archive_name(&self) -> String {
+        format!("dory-params{}", self.extension())
+    }
+
+    /// The verifier archive name used when none is given, e.g. `dory-verifier-params.tar.gz`.
+    pub fn default_verifier_archive_name(&self) -> String {
+        format!("dory-verifier-params{}", self.extension())
+    }
+
+    /// The level used when none is given.
+    pub fn default_level(&self) -> u32 {
+        match self {
+            ArchiveFormat::Tar => 0,
+            ArchiveFormat::TarGz | ArchiveFormat::TarXz => 6,
+            ArchiveFormat::TarZst => 3,
+        }
+    }
+
+    /// The highest level the codec accepts.
+    pub fn max_level(&self) -> u32 {
+        match self {
+            ArchiveFormat::Tar => 0,
+            ArchiveFormat::TarGz | ArchiveFormat::TarXz => 9,
+            ArchiveFormat::TarZst => 22,
+        }
+    }
+
+    /// Detect the format of the archive at `path` from its leading bytes.
+    pub fn detect(path: &Path) -> io::Result<Self> {
+        let mut start = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+        File::open(path)?
+            .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+            .read_to_end(&mut start)?;
+        Self::from_magic(&start).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a tar, gzip, zstd or xz archive", path.display()),
+            )
+        })
+    }
+
+    // Recognize a format by the magic bytes at the start of the file
+    fn from_magic(start: &[u8]) -> Option<Self> {
+        if start.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else if start.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(ArchiveFormat::TarZst)
+        } else if start.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Some(ArchiveFormat::TarXz)
+        } else if start.get(TAR_MAGIC_OFFSET..) == Some(TAR_MAGIC) {
+            Some(ArchiveFormat::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+// POSIX and GNU tar headers both start their magic with "ustar" at this offset
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_MAGIC: &[u8] = b"ustar";
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.extension()[1..])
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.to_string() == s)
+            .ok_or_else(|| format!("expected tar, tar.gz, tar.zst or tar.xz, found {:?}", s))
+    }
+}
+
+/// How archives are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionOptions {
+    /// The archive format
+    pub format: ArchiveFormat,
+    /// Codec level, or the format's default level
+    pub level: Option<u32>,
+    /// Threads compressing at once
+    pub threads: usize,
+}
+
+impl CompressionOptions {
+    /// The format's default level on every available core.
+    pub fn new(format: ArchiveFormat) -> Self {
+        Self {
+            format,
+            level: None,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    /// The level to compress with.
+    pub fn level(&self) -> u32 {
+        self.level.unwrap_or(self.format.default_level())
+    }
+
+    /// Fail with `InvalidInput` if the format's codec does not support the level.
+    pub fn check_level(&self) -> io::Result<()> {
+        let level = self.level();
+        if level > self.format.max_level() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} supports compression levels up to {}, found {}",
+                    self.format,
+                    self.format.max_level(),
+                    level
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CompressionOptions {
+    fn default() -> Self {
+        Self::new(ArchiveFormat::TarGz)
+    }
+}
+
+// A writer compressing in the chosen format
+enum Compressor<W: Write> {
+    Tar(W),
+    Gz(ParallelGzEncoder<W>),
+    Zst(zstd::stream::write::Encoder<'static, W>),
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> Compressor<W> {
+    fn new(inner: W, options: CompressionOptions) -> io::Result<Self> {
+        options.check_level()?;
+        let level = options.level();
+        let threads = options.threads.max(1);
+        Ok(match options.format {
+            ArchiveFormat::Tar => Compressor::Tar(inner),
+            ArchiveFormat::TarGz => Compressor::Gz(ParallelGzEncoder::new(inner, level, threads)?),
+            ArchiveFormat::TarZst => {
+                let mut encoder = zstd::stream::write::Encoder::new(inner, level as i32)?;
+                encoder.include_checksum(true)?;
+                // Multithreaded output does not depend on the number of workers, but differs
+                // from single-threaded output, so even one thread runs as a worker
+                encoder.multithread(threads as u32)?;
+                Compressor::Zst(encoder)
+            }
+            ArchiveFormat::TarXz => {
+                let stream = MtStreamBuilder::new()
+                    .threads(threads as u32)
+                    .preset(level)
+                    .check(Check::Crc64)
+                    .encoder()?;
+                Compressor::Xz(XzEncoder::new_stream(inner, stream))
+            }
+        })
+    }
+
+    // Write any buffered data and the codec's trailer, returning the underlying writer
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Compressor::Tar(inner) => Ok(inner),
+            Compressor::Gz(encoder) => encoder.finish(),
+            Compressor::Zst(encoder) => encoder.finish(),
+            Compressor::Xz(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::Tar(inner) => inner.write(buf),
+            Compressor::Gz(encoder) => encoder.write(buf),
+            Compressor::Zst(encoder) => encoder.write(buf),
+            Compressor::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::Tar(inner) => inner.flush(),
+            Compressor::Gz(encoder) => encoder.flush(),
+            Compressor::Zst(encoder) => encoder.flush(),
+            Compressor::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Open `archive_path` for reading, decompressing it according to its magic bytes.
+pub fn open_archive(archive_path: &Path) -> io::Result<Archive<Box<dyn Read>>> {
+    Ok(Archive::new(decompress(archive_path)?))
+}
+
+// The uncompressed tar stream of an archive in any supported format
+pub(crate) fn decompress(archive_path: &Path) -> io::Result<Box<dyn Read>> {
+    let format = ArchiveFormat::detect(archive_path)?;
+    let file = BufReader::new(File::open(archive_path)?);
+    Ok(match format {
+        ArchiveFormat::Tar => Box::new(file),
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(file)),
+        ArchiveFormat::TarZst => Box::new(zstd::stream::read::Decoder::new(file)?),
+        ArchiveFormat::TarXz => Box::new(XzDecoder::new_multi_decoder(file)),
+    })
+}
+
+/// The archive name for one of several `nu` tiers, e.g. `dory-params-nu16.tar.gz`.
+pub fn archive_name_for_nu(archive_name: &str, nu: usize) -> String {
+    let (stem, extension) = match archive_name.find(".tar") {
+        Some(i) => archive_name.split_at(i),
+        None => (archive_name, ""),
+    };
+    format!("{}-nu{}{}", stem, nu, extension)
+}
+
+/// Streams members into a new archive, hashing each one on the way in.
+///
+/// Every member's size must be known before it is written, since tar records it in the header.
+/// Member bytes advance the current phase of `progress`, and the compressed size written so far
+/// is reported as it grows.
+///
+/// The output depends only on the members, the order they are appended in and the compression
+/// format and level. Headers record no owner, the same mode for every member and a fixed
+/// modification time, taken from [`SOURCE_DATE_EPOCH`] when it is set and zero otherwise, and
+/// every codec writes the same bytes for any thread count.
+pub struct ArchiveWriter<'a> {
+    builder: Builder<Compressor<HashingWriter<CountingWriter<File, ReportFn<'a>>>>>,
+    // The archive's file name, naming its digest
+    name: String,
+    mtime: u64,
+    progress: &'a dyn Progress,
+    // Directory every member is also written into, if any
+    copy_dir: Option<PathBuf>,
+}
+
+type ReportFn<'a> = Box<dyn FnMut(u64, u64) + Send + 'a>;
+
+impl<'a> ArchiveWriter<'a> {
+    /// Create the archive file at `archive_path`.
+    pub fn create(
+        archive_path: &Path,
+        compression: CompressionOptions,
+        progress: &'a dyn Progress,
+    ) -> io::Result<Self> {
+        let mtime = source_date_epoch()?;
+        let report: ReportFn<'a> = Box::new(move |_, total| progress.compressed(total));
+        let archive_file = HashingWriter {
+            inner: CountingWriter::new(File::create(archive_path)?, report),
+            context: Context::new(&SHA256),
+            written: 0,
+        };
+        Ok(Self {
+            builder: Builder::new(Compressor::new(archive_file, compression)?),
+            name: archive_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            mtime,
+            progress,
+            copy_dir: None,
+        })
+    }
+
+    /// Also write every member appended from now on to a file of the same name in `dir`.
+    pub fn copy_members_into(&mut self, dir: &Path) {
+        self.copy_dir = Some(dir.to_path_buf());
+    }
+
+    /// Append exactly `size` bytes read from `data` as the member `name`.
+    pub fn append(
+        &mut self,
+        name: &str,
+        size: u64,
+        mut data: impl Read,
+    ) -> io::Result<ManifestEntry> {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(MEMBER_MODE);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_mtime(self.mtime);
+        let copy = match &self.copy_dir {
+            Some(dir) => Some(BufWriter::new(File::create(dir.join(name))?)),
+            None => None,
+        };
+        let mut hashing = HashingReader {
+            inner: (&mut data).take(size),
+            context: Context::new(&SHA256),
+            read: 0,
+            progress: self.progress,
+            copy,
+        };
+        self.builder
+            .append_data(&mut header, name, &mut hashing)
+            .map_err(|e| io::Error::new(e.kind(), format!("adding {}: {}", name, e)))?;
+        let HashingReader {
+            read,
+            context,
+            copy,
+            ..
+        } = hashing;
+        if let Some(mut copy) = copy {
+            copy.flush()?;
+        }
+        // Drain anything past the recorded size so a writer on the other end can finish
+        let read = read + io::copy(&mut data, &mut io::sink())?;
+        if read != size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is {} bytes, expected {}", name, read, size),
+            ));
+        }
+        Ok(ManifestEntry {
+            name: name.to_string(),
+            size,
+            sha256: hex::encode(context.finish().as_ref()),
+        })
+    }
+
+    /// Append the file at `path` as the member `name`.
+    pub fn append_file(&mut self, name: &str, path: &Path) -> io::Result<ManifestEntry> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        self.append(name, size, BufReader::new(file))
+    }
+
+    /// Append the `size` bytes that `write` writes to the writer it is given as the member
+    /// `name`.
+    ///
+    /// `write` runs on its own thread and feeds the archive through an in-process pipe, so the
+    /// member is streamed without being buffered in memory or on disk.
+    pub fn append_streamed(
+        &mut self,
+        name: &str,
+        size: u64,
+        write: impl FnOnce(&mut dyn Write) -> io::Result<()> + Send,
+    ) -> io::Result<ManifestEntry> {
+        let (mut pipe, write_end) = io::pipe()?;
+        thread::scope(|scope| {
+            // The reader sees the end of the member once the writer returns and drops its end
+            let writer = scope.spawn(move || {
+                let mut write_end = BufWriter::with_capacity(1 << 20, write_end);
+                write(&mut write_end)?;
+                write_end.flush()
+            });
+            let result = self.append(name, size, &mut pipe);
+            if result.is_err() {
+                // Keep the writer from blocking on a pipe nobody reads; the append's error is
+                // the one reported
+                let _ = io::copy(&mut pipe, &mut io::sink());
+            }
+            // The writer's own error explains a short member better than the reader's
+            writer
+                .join()
+                .map_err(|_| io::Error::other(format!("writing {} panicked", name)))??;
+            result
+        })
+    }
+
+    /// Append the `size` bytes that `write` writes to the path it is given as the member `name`.
+    ///
+    /// Only for writers that accept nothing but a path, like blitzar's. The path is a named pipe
+    /// inside `dir` that is read straight into the archive, so the member never lands on disk.
+    pub fn append_written(
+        &mut self,
+        name: &str,
+        size: u64,
+        dir: &Path,
+        write: impl FnOnce(&Path) -> io::Result<()>,
+    ) -> io::Result<ManifestEntry> {
+        let fifo = dir.join(name);
+        make_fifo(&fifo)?;
+        let (pipe, write_end) = open_fifo(&fifo)?;
+        let result = thread::scope(|scope| {
+            let reader = scope.spawn(move || {
+                let mut pipe = BufReader::new(pipe);
+                let result = self.append(name, size, &mut pipe);
+                if result.is_err() {
+                    // Keep the writer from blocking on a pipe nobody reads
+                    io::copy(&mut pipe, &mut io::sink())?;
+                }
+                result
+            });
+            // A panicking writer must still release the reader
+            let written = panic::catch_unwind(AssertUnwindSafe(|| write(&fifo)));
+            // The reader sees the end of the member once no write end is left open
+            drop(write_end);
+            let result = reader.join().expect("the archive reader does not panic");
+            // The writer's own error explains a short member better than the reader's
+            written.map_err(|_| io::Error::other(format!("writing {} panicked", name)))??;
+            result
+        });
+        fs::remove_file(&fifo)?;
+        result
+    }
+
+    /// Finish the tar stream and the compressed stream, and sync the file to disk.
+    ///
+    /// Returns the archive's size and SHA-256, hashed as it was written and named by its file
+    /// name, so the finished archive never has to be read back to fingerprint it.
+    pub fn finish(self) -> io::Result<ManifestEntry> {
+        // Finalize the tar archive, then the compressed stream so its trailer errors are not lost
+        let HashingWriter {
+            inner,
+            context,
+            written,
+        } = self.builder.into_inner()?.finish()?;
+        inner.into_inner().sync_all()?;
+        Ok(ManifestEntry {
+            name: self.name,
+            size: written,
+            sha256: hex::encode(context.finish().as_ref()),
+        })
+    }
+}
+
+/// The member modification time: [`SOURCE_DATE_EPOCH`] if set, following
+/// <https://reproducible-builds.org/specs/source-date-epoch/>, and zero otherwise.
+pub fn source_date_epoch() -> io::Result<u64> {
+    match env::var(SOURCE_DATE_EPOCH) {
+        Ok(value) if !value.is_empty() => value.parse().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} must be a whole number of seconds, found {:?}",
+                    SOURCE_DATE_EPOCH, value
+                ),
+            )
+        }),
+        _ => Ok(0),
+    }
+}
+
+// Hashes and counts the bytes written through it
+struct HashingWriter<W> {
+    inner: W,
+    context: Context,
+    written: u64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.context.update(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Hashes and counts the bytes read through it, advancing `progress` and writing them to
+// `copy` if given
+struct HashingReader<'a, R> {
+    inner: R,
+    context: Context,
+    read: u64,
+    progress: &'a dyn Progress,
+    copy: Option<BufWriter<File>>,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.context.update(&buf[..n]);
+        if let Some(copy) = &mut self.copy {
+            copy.write_all(&buf[..n])?;
+        }
+        self.read += n as u64;
+        self.progress.advance(n as u64);
+        Ok(n)
+    }
+}
+
+// Open both ends of the named pipe at `path` without waiting for a partner.
+//
+// Holding a write end open means the read end only reaches end of file once the real writer
+// has opened, written and closed the pipe, or has failed without ever opening it.
+fn open_fifo(path: &Path) -> io::Result<(File, File)> {
+    // Opening the read end would otherwise block until a writer appears
+    let read_end = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)?;
+    let fd = read_end.as_raw_fd();
+    // SAFETY: `fd` is an open descriptor owned by `read_end`
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags & !libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let write_end = OpenOptions::new().write(true).open(path)?;
+    Ok((read_end, write_end))
+}
+
+// Create a named pipe at `path`, readable and writable only by this user
+fn make_fifo(path: &Path) -> io::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `c_path` is a valid NUL-terminated string for the duration of the call
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// Write the given files from `dir` into a new archive, named by their file names, reporting
+// the member bytes added and the compressed bytes written
+pub fn create_archive(
+    archive_path: &Path,
+    dir: &Path,
+    members: &[&str],
+    compression: CompressionOptions,
+    progress: &dyn Progress,
+) -> io::Result<()> {
+    let member_sizes = members
+        .iter()
+        .map(|member| fs::metadata(dir.join(member)).map(|metadata| metadata.len()))
+        .collect::<io::Result<Vec<_>>>()?;
+    progress.start(Phase::Archive, Some(member_sizes.iter().sum()));
+
+    let mut archive = ArchiveWriter::create(archive_path, compression, progress)?;
+    for member in members {
+        archive.append_file(member, &dir.join(member))?;
+    }
+    archive.finish()?;
+    progress.finish();
+    Ok(())
+}
+
+// Decompress and unpack an archive in any supported format into `dest`
+pub fn unpack_archive(archive_path: &Path, dest: &Path) -> io::Result<()> {
+    open_archive(archive_path)?.unpack(dest)
+}
+
+// Compare two files chunk by chunk, returning the offset of the first difference if any
+pub fn first_difference(a: &Path, b: &Path) -> io::Result<Option<u64>> {
+    const CHUNK: usize = 1 << 20;
+    let mut reader_a = BufReader::new(File::open(a)?);
+    let mut reader_b = BufReader::new(File::open(b)?);
+    let (mut buf_a, mut buf_b) = (vec![0u8; CHUNK], vec![0u8; CHUNK]);
+    let mut offset = 0u64;
+    loop {
+        let read_a = read_full(&mut reader_a, &mut buf_a)?;
+        let read_b = read_full(&mut reader_b, &mut buf_b)?;
+        if let Some(i) = buf_a[..read_a.min(read_b)]
+            .iter()
+            .zip(&buf_b[..read_a.min(read_b)])
+            .position(|(x, y)| x != y)
+        {
+            return Ok(Some(offset + i as u64));
+        }
+        if read_a != read_b {
+            return Ok(Some(offset + read_a.min(read_b) as u64));
+        }
+        if read_a == 0 {
+            return Ok(None);
+        }
+        offset += read_a as u64;
+    }
+}
+
+// Fill `buf` as far as possible, returning fewer bytes only at end of input
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
@@ -0,0 +1,31 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// Save `path` by writing it to a temporary sibling, syncing that and renaming it into place,
+/// so an interrupted save leaves the old file or the new one but never a truncated one.
+pub fn save_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let save = || {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        write(&mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp_path, path)
+    };
+    let result = save();
+    if result.is_err() {
+        // The old file, if any, is untouched; only the partial copy is cleaned up
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
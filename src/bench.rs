@@ -0,0 +1,91 @@
+//! Measure how well each archive format compresses a bundle, and how fast.
+
+use crate::{
+    archive::{create_archive, decompress, unpack_archive, ArchiveFormat, CompressionOptions},
+    progress::Progress,
+    temp_dir::TempDir,
+};
+use std::{
+    fs, io,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// The cost and benefit of one format on one bundle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodecBenchmark {
+    pub format: ArchiveFormat,
+    pub level: u32,
+    /// Size of the uncompressed tar stream
+    pub uncompressed_size: u64,
+    pub compressed_size: u64,
+    /// Wall time to write the archive, including reading the members from disk
+    pub compress_time: Duration,
+    /// Wall time to decompress the whole tar stream
+    pub decompress_time: Duration,
+}
+
+impl CodecBenchmark {
+    /// Compressed size as a fraction of the uncompressed size.
+    pub fn ratio(&self) -> f64 {
+        self.compressed_size as f64 / self.uncompressed_size as f64
+    }
+
+    /// Uncompressed bytes compressed per second.
+    pub fn compress_throughput(&self) -> f64 {
+        self.uncompressed_size as f64 / self.compress_time.as_secs_f64()
+    }
+
+    /// Uncompressed bytes produced per second when decompressing.
+    pub fn decompress_throughput(&self) -> f64 {
+        self.uncompressed_size as f64 / self.decompress_time.as_secs_f64()
+    }
+}
+
+/// Unpack `archive` and recompress its members in every format at its default level.
+///
+/// Each archive is written to a temporary directory next to the unpacked members, timed, read
+/// back in full and deleted before the next format starts, so at most one copy exists at once.
+pub fn bench_compression(
+    archive: &Path,
+    threads: usize,
+    progress: &dyn Progress,
+) -> io::Result<Vec<CodecBenchmark>> {
+    let work_dir = TempDir::new("dory-bench")?;
+    let members_dir = work_dir.join("members");
+    unpack_archive(archive, &members_dir)?;
+    let mut members = fs::read_dir(&members_dir)?
+        .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+        .collect::<io::Result<Vec<_>>>()?;
+    members.sort();
+    let members: Vec<&str> = members.iter().map(String::as_str).collect();
+
+    let mut benchmarks = Vec::new();
+    for format in ArchiveFormat::ALL {
+        let options = CompressionOptions {
+            threads,
+            ..CompressionOptions::new(format)
+        };
+        let path = work_dir.join(&format!("bench{}", format.extension()));
+
+        let start_time = Instant::now();
+        create_archive(&path, &members_dir, &members, options, progress)?;
+        let compress_time = start_time.elapsed();
+        let compressed_size = fs::metadata(&path)?.len();
+
+        let start_time = Instant::now();
+        let uncompressed_size = io::copy(&mut decompress(&path)?, &mut io::sink())?;
+        let decompress_time = start_time.elapsed();
+        fs::remove_file(&path)?;
+
+        benchmarks.push(CodecBenchmark {
+            format,
+            level: options.level(),
+            uncompressed_size,
+            compressed_size,
+            compress_time,
+            decompress_time,
+        });
+    }
+    Ok(benchmarks)
+}
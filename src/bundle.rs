@@ -0,0 +1,712 @@
+use crate::{
+    archive::{
+        open_archive, ArchiveWriter, CompressionOptions, BLITZAR_HANDLE_FILE,
+        PUBLIC_PARAMETERS_FILE, VERIFIER_SETUP_FILE,
+    },
+    error::ParamGenError,
+    hash_to_curve::GeneratorHasher,
+    manifest::{self, Manifest, ManifestEntry, MANIFEST_FILE, SIGNATURE_FILE},
+    metadata::{BundleMetadata, METADATA_FILE},
+    progress::{Phase, Progress},
+    raw_parameters::RawParameters,
+    seed::Seed,
+    sizes::{nu_of, ArtifactSizes, BLITZAR_PARTITION_WIDTH},
+    temp_dir::TempDir,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use blitzar::compute::{ElementP2, MsmHandle};
+use proof_of_sql::proof_primitive::dory::{ProverSetup, PublicParameters, VerifierSetup};
+use ring::signature::Ed25519KeyPair;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    os::unix::fs::FileTypeExt,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+/// The blitzar MSM handle over the `Γ_1` generators.
+pub type BlitzarHandle = MsmHandle<ElementP2<ark_bls12_381::g1::Config>>;
+
+/// Where and how [`write_bundle`] writes its archives.
+pub struct BundleOptions {
+    /// Seed the parameters were generated from, recorded in the metadata and manifest
+    pub seed: Seed,
+    /// Directory the archives are moved into once complete
+    pub out_dir: PathBuf,
+    /// File name of the prover archive
+    pub archive_name: String,
+    /// File name of the verifier archive, written only when `verifier_setup` is set
+    pub verifier_archive_name: String,
+    /// Also derive the verifier setup and package it in its own archive
+    pub verifier_setup: bool,
+    /// Domain-separation tag the generators were hashed to the curve with, if not seeded
+    pub hash_to_curve_dst: Option<String>,
+    /// The archive these parameters were extended or truncated from, recorded in the manifest
+    pub parent_archive: Option<ManifestEntry>,
+    /// Also write each archive's members, uncompressed, into a directory named after it
+    pub keep_intermediates: bool,
+    /// Format, level and thread count for the archives; the archive names are used as given
+    pub compression: CompressionOptions,
+    /// Key to sign each archive's manifest with
+    pub signing_key: Option<Ed25519KeyPair>,
+}
+
+impl BundleOptions {
+    /// Default file names in the current directory, without a verifier setup or signature.
+    pub fn new(seed: Seed) -> Self {
+        Self {
+            seed,
+            out_dir: PathBuf::from("."),
+            archive_name: "dory-params.tar.gz".to_string(),
+            verifier_archive_name: "dory-verifier-params.tar.gz".to_string(),
+            verifier_setup: false,
+            hash_to_curve_dst: None,
+            parent_archive: None,
+            keep_intermediates: false,
+            compression: CompressionOptions::default(),
+            signing_key: None,
+        }
+    }
+
+    /// Check the archive names with [`check_archive_names`].
+    pub fn check_archive_names(&self) -> Result<(), ParamGenError> {
+        check_archive_names(
+            &self.archive_name,
+            &self.verifier_archive_name,
+            self.verifier_setup,
+        )
+    }
+}
+
+/// Check that the archive names are plain file names, so the archives land in the output
+/// directory, and that the verifier archive, if written, does not replace the prover archive.
+pub fn check_archive_names(
+    archive_name: &str,
+    verifier_archive_name: &str,
+    verifier_setup: bool,
+) -> Result<(), ParamGenError> {
+    let invalid = |name: &str, reason: &str| ParamGenError::Archive {
+        path: PathBuf::from(name),
+        source: io::Error::new(io::ErrorKind::InvalidInput, reason.to_string()),
+    };
+    let mut names = vec![archive_name];
+    if verifier_setup {
+        names.push(verifier_archive_name);
+    }
+    for name in &names {
+        if Path::new(name).file_name() != Some(name.as_ref()) {
+            return Err(invalid(name, "archive names must be plain file names"));
+        }
+    }
+    if verifier_setup && archive_name == verifier_archive_name {
+        return Err(invalid(
+            archive_name,
+            "the prover and verifier archives need different names",
+        ));
+    }
+    Ok(())
+}
+
+/// What [`write_bundle`] produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleOutput {
+    /// Final paths of the written archives, prover archive first
+    pub archives: Vec<PathBuf>,
+    /// The size and SHA-256 of each archive in the same order, hashed as it was written and
+    /// named by its file name
+    pub digests: Vec<ManifestEntry>,
+    /// The directory of members kept next to each archive, if intermediates were kept
+    pub intermediates: Vec<PathBuf>,
+    /// Hex public key matching the manifest signatures, if they were signed
+    pub public_key: Option<String>,
+}
+
+/// Generate the Dory public parameters for `nu` from `seed`.
+///
+/// The generators are drawn in chunks so `progress` sees them being produced; the result is
+/// identical to `PublicParameters::rand` with the seed's RNG.
+pub fn generate(
+    nu: usize,
+    seed: &Seed,
+    progress: &dyn Progress,
+) -> Result<PublicParameters, ParamGenError> {
+    catch_generation(|| RawParameters::rand(nu, &mut seed.rng(), progress))?
+        .to_public_parameters()
+        .map_err(|e| ParamGenError::Generation(e.to_string()))
+}
+
+/// Derive the Dory public parameters for `nu` by hashing to the curve under `dst`.
+pub fn generate_hash_to_curve(
+    nu: usize,
+    dst: &str,
+    progress: &dyn Progress,
+) -> Result<PublicParameters, ParamGenError> {
+    let hasher = GeneratorHasher::new(dst).map_err(ParamGenError::Generation)?;
+    catch_generation(|| hasher.derive(nu, progress))?
+        .to_public_parameters()
+        .map_err(|e| ParamGenError::Generation(e.to_string()))
+}
+
+/// Build the prover setup, including the blitzar handle, for a set of public parameters.
+///
+/// `ProverSetup::from` offers no hooks, so `progress` only sees the phase start and finish.
+pub fn prover_setup<'a>(
+    public_parameters: &'a PublicParameters,
+    progress: &dyn Progress,
+) -> Result<ProverSetup<'a>, ParamGenError> {
+    progress.start(Phase::ProverSetup, None);
+    let prover_setup = catch_generation(|| ProverSetup::from(public_parameters));
+    progress.finish();
+    prover_setup
+}
+
+/// Prover setups for the tiers of one generation that precompute only the largest handle.
+///
+/// A partition of the blitzar handle depends only on the `BLITZAR_PARTITION_WIDTH` generators
+/// it covers, so the handle for a prefix of the generators is a prefix of the larger handle.
+/// The exception is a smaller `nu` whose last partition is not full, since blitzar pads it;
+/// those tiers are below 16 generators and cheap to build directly.
+pub struct TierHandles {
+    dir: TempDir,
+    // The `nu` and file of the largest handle written so far
+    largest: Option<(usize, PathBuf)>,
+}
+
+impl TierHandles {
+    /// Keep any handle written by [`TierHandles::keep`] in a private directory inside `dir`.
+    pub fn new_in(dir: &Path) -> io::Result<Self> {
+        Ok(Self {
+            dir: TempDir::new_in(dir, ".dory-handles")?,
+            largest: None,
+        })
+    }
+
+    /// The prover setup for `public_parameters` with its handle loaded from a prefix of the
+    /// largest handle, or `None` if it must be built because no larger handle covers it.
+    pub fn cut<'a>(
+        &self,
+        public_parameters: &'a PublicParameters,
+        progress: &dyn Progress,
+    ) -> Result<Option<ProverSetup<'a>>, ParamGenError> {
+        let nu = nu_of(public_parameters);
+        let Some((_, largest)) = self.largest.as_ref().filter(|(largest_nu, _)| {
+            nu < *largest_nu && (1u64 << nu).is_multiple_of(BLITZAR_PARTITION_WIDTH)
+        }) else {
+            return Ok(None);
+        };
+        let path = self.dir.join(&format!("blitzar_handle-nu{}.bin", nu));
+        let handle_error = |e: io::Error| ParamGenError::BlitzarHandle {
+            path: path.clone(),
+            reason: e.to_string(),
+        };
+        progress.start(Phase::ProverSetup, None);
+        let size = ArtifactSizes::for_nu(nu).blitzar_handle;
+        let copy = || {
+            let mut writer = BufWriter::new(File::create(&path)?);
+            let copied = io::copy(&mut File::open(largest)?.take(size), &mut writer)?;
+            writer.flush()?;
+            Ok(copied)
+        };
+        let copied = copy().map_err(handle_error)?;
+        if copied != size {
+            return Err(handle_error(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("{} holds only {} bytes", largest.display(), copied),
+            )));
+        }
+        let blitzar_handle = BlitzarHandle::new_from_file(&path.to_string_lossy());
+        fs::remove_file(&path).map_err(handle_error)?;
+        progress.finish();
+        Ok(Some(
+            ProverSetup::from_public_parameters_and_blitzar_handle(
+                public_parameters,
+                blitzar_handle,
+            ),
+        ))
+    }
+
+    /// Write the freshly built handle for `nu` into the private directory, to cut smaller
+    /// tiers from, unless a larger handle is already kept.
+    pub fn keep(
+        &mut self,
+        nu: usize,
+        blitzar_handle: &BlitzarHandle,
+        progress: &dyn Progress,
+    ) -> Result<(), ParamGenError> {
+        if self.covers(nu) {
+            return Ok(());
+        }
+        let path = self.dir.join(&format!("blitzar_handle-nu{}.bin", nu));
+        progress.start(Phase::BlitzarHandle, None);
+        let written = write_blitzar_handle(
+            blitzar_handle,
+            &path,
+            ArtifactSizes::for_nu(nu).blitzar_handle,
+        );
+        progress.finish();
+        written.map_err(|e| ParamGenError::BlitzarHandle {
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        self.offer(nu, path);
+        Ok(())
+    }
+
+    /// Cut smaller tiers from the complete handle for `nu` already written at `path`, e.g. a
+    /// checkpoint, unless a larger handle is already kept.
+    pub fn offer(&mut self, nu: usize, path: PathBuf) {
+        if !self.covers(nu) {
+            self.largest = Some((nu, path));
+        }
+    }
+
+    /// Whether a handle for `nu` or a larger `nu` is already kept.
+    pub fn covers(&self, nu: usize) -> bool {
+        self.largest
+            .as_ref()
+            .is_some_and(|(largest_nu, _)| *largest_nu >= nu)
+    }
+}
+
+/// Serialize the artifacts into archives under `opts.out_dir`.
+///
+/// Every artifact is streamed straight into its archive, so no intermediate `.bin` files are
+/// written unless `opts.keep_intermediates` asks for a copy of the members. Each archive, and
+/// any such copy, is written inside a private staging directory in `opts.out_dir` and renamed
+/// into place only once complete, so concurrent runs never see partial files.
+pub fn write_bundle(
+    prover_setup: ProverSetup,
+    public_parameters: &PublicParameters,
+    opts: &BundleOptions,
+    progress: &dyn Progress,
+) -> Result<BundleOutput, ParamGenError> {
+    opts.check_archive_names()?;
+    let nu = nu_of(public_parameters);
+    let sizes = ArtifactSizes::for_nu(nu);
+    fs::create_dir_all(&opts.out_dir).map_err(|source| ParamGenError::OutputDir {
+        path: opts.out_dir.clone(),
+        source,
+    })?;
+    let staging = TempDir::new_in(&opts.out_dir, ".dory-staging").map_err(|source| {
+        ParamGenError::OutputDir {
+            path: opts.out_dir.clone(),
+            source,
+        }
+    })?;
+
+    // Record how the parameters were generated
+    let metadata =
+        BundleMetadata::for_generation(nu, &opts.seed, opts.hash_to_curve_dst.as_deref());
+    let metadata_json =
+        serde_json::to_vec_pretty(&metadata).map_err(|e| ParamGenError::Serialization {
+            path: PathBuf::from(METADATA_FILE),
+            source: e.into(),
+        })?;
+
+    let archive_path = staging.join(&opts.archive_name);
+    let mut archive =
+        ArchiveWriter::create(&archive_path, opts.compression, progress).map_err(|source| {
+            ParamGenError::Archive {
+                path: archive_path.clone(),
+                source,
+            }
+        })?;
+    keep_members(&mut archive, &staging, &opts.archive_name, opts)?;
+
+    progress.start(Phase::PublicParameters, Some(sizes.public_parameters));
+    let public_parameters_entry = archive
+        .append_streamed(PUBLIC_PARAMETERS_FILE, sizes.public_parameters, |writer| {
+            public_parameters
+                .serialize_with_mode(writer, Compress::No)
+                .map_err(|e| io::Error::other(e.to_string()))
+        })
+        .map_err(|source| ParamGenError::Serialization {
+            path: PathBuf::from(PUBLIC_PARAMETERS_FILE),
+            source,
+        })?;
+    progress.finish();
+
+    progress.start(Phase::BlitzarHandle, Some(sizes.blitzar_handle));
+    let blitzar_handle_entry = archive
+        .append_written(
+            BLITZAR_HANDLE_FILE,
+            sizes.blitzar_handle,
+            staging.path(),
+            |path| write_blitzar_handle(&prover_setup.blitzar_handle(), path, sizes.blitzar_handle),
+        )
+        .map_err(|e| ParamGenError::BlitzarHandle {
+            path: PathBuf::from(BLITZAR_HANDLE_FILE),
+            reason: e.to_string(),
+        })?;
+    progress.finish();
+
+    let mut staged = vec![seal(
+        archive,
+        &archive_path,
+        vec![public_parameters_entry, blitzar_handle_entry],
+        &metadata_json,
+        nu,
+        opts,
+        progress,
+    )?];
+
+    // Derive the verifier setup if requested; it is small enough to serialize in memory
+    if opts.verifier_setup {
+        progress.start(Phase::VerifierSetup, None);
+        let verifier_setup = catch_generation(|| VerifierSetup::from(public_parameters))?;
+        let mut verifier_setup_bytes = Vec::with_capacity(sizes.verifier_setup as usize);
+        verifier_setup
+            .serialize_with_mode(&mut verifier_setup_bytes, Compress::No)
+            .map_err(|e| ParamGenError::Serialization {
+                path: PathBuf::from(VERIFIER_SETUP_FILE),
+                source: io::Error::other(e.to_string()),
+            })?;
+        progress.finish();
+
+        let verifier_archive_path = staging.join(&opts.verifier_archive_name);
+        let archive_error = |source| ParamGenError::Archive {
+            path: verifier_archive_path.clone(),
+            source,
+        };
+        let mut archive = ArchiveWriter::create(&verifier_archive_path, opts.compression, progress)
+            .map_err(archive_error)?;
+        keep_members(&mut archive, &staging, &opts.verifier_archive_name, opts)?;
+        let verifier_setup_entry = archive
+            .append(
+                VERIFIER_SETUP_FILE,
+                verifier_setup_bytes.len() as u64,
+                &verifier_setup_bytes[..],
+            )
+            .map_err(archive_error)?;
+        staged.push(seal(
+            archive,
+            &verifier_archive_path,
+            vec![verifier_setup_entry],
+            &metadata_json,
+            nu,
+            opts,
+            progress,
+        )?);
+    }
+
+    // Move the finished archives into place; a rename within one filesystem is atomic
+    let mut archives = Vec::with_capacity(staged.len());
+    let mut digests = Vec::with_capacity(staged.len());
+    let mut intermediates = Vec::new();
+    for (archive, digest) in staged {
+        let file_name = archive.file_name().expect("archive paths have a file name");
+        let destination = opts.out_dir.join(file_name);
+        fs::rename(&archive, &destination).map_err(|source| ParamGenError::Archive {
+            path: destination.clone(),
+            source,
+        })?;
+        archives.push(destination);
+        digests.push(digest);
+
+        if opts.keep_intermediates {
+            let dir_name = intermediates_dir_name(&file_name.to_string_lossy(), opts);
+            let destination = opts.out_dir.join(&dir_name);
+            move_files(&staging.join(&dir_name), &destination).map_err(|source| {
+                ParamGenError::OutputDir {
+                    path: destination.clone(),
+                    source,
+                }
+            })?;
+            intermediates.push(destination);
+        }
+    }
+
+    let staging_path = staging.path().to_path_buf();
+    staging.remove().map_err(|source| ParamGenError::Cleanup {
+        path: staging_path,
+        source,
+    })?;
+    Ok(BundleOutput {
+        archives,
+        digests,
+        intermediates,
+        public_key: opts.signing_key.as_ref().map(manifest::public_key_hex),
+    })
+}
+
+/// Write `blitzar_handle` to `path` and check that exactly `expected_len` bytes arrived.
+///
+/// blitzar only writes a handle to a path and reports no errors, so the size written is what
+/// shows whether it succeeded. A regular file is also synced to disk. A named pipe cannot be
+/// measured afterwards, so its reader is left to count the bytes instead.
+pub(crate) fn write_blitzar_handle(
+    blitzar_handle: &BlitzarHandle,
+    path: &Path,
+    expected_len: u64,
+) -> io::Result<()> {
+    blitzar_handle.write(&path.to_string_lossy());
+    let metadata = fs::metadata(path)?;
+    if metadata.file_type().is_fifo() {
+        return Ok(());
+    }
+    if metadata.len() != expected_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "blitzar wrote {} bytes to {}, expected {}",
+                metadata.len(),
+                path.display(),
+                expected_len
+            ),
+        ));
+    }
+    File::open(path)?.sync_all()
+}
+
+/// A prover archive loaded into memory, owning its public parameters.
+///
+/// blitzar can only load a handle from a path, so the archive's handle is kept in a private
+/// temporary directory for as long as the bundle lives, and removed when it is dropped.
+pub struct LoadedBundle {
+    public_parameters: PublicParameters,
+    handle_dir: TempDir,
+}
+
+impl LoadedBundle {
+    /// The archive's public parameters.
+    pub fn public_parameters(&self) -> &PublicParameters {
+        &self.public_parameters
+    }
+
+    /// The private copy of the archive's blitzar handle.
+    pub fn blitzar_handle_path(&self) -> PathBuf {
+        self.handle_dir.join(BLITZAR_HANDLE_FILE)
+    }
+
+    /// A ready-to-use prover setup borrowing the bundle's public parameters.
+    pub fn prover_setup(&self) -> ProverSetup<'_> {
+        let blitzar_handle =
+            BlitzarHandle::new_from_file(&self.blitzar_handle_path().to_string_lossy());
+        ProverSetup::from_public_parameters_and_blitzar_handle(
+            &self.public_parameters,
+            blitzar_handle,
+        )
+    }
+}
+
+/// Load the public parameters and blitzar handle from a prover archive.
+///
+/// The archive is streamed through the decompressor and the public parameters are deserialized
+/// straight from their tar entry. blitzar can only read a handle from a path, so that entry is
+/// copied to a private temporary file which is removed before returning.
+pub fn load_bundle_parts(
+    archive_path: &Path,
+) -> Result<(PublicParameters, BlitzarHandle), ParamGenError> {
+    let bundle = load_bundle(archive_path)?;
+    let blitzar_handle =
+        BlitzarHandle::new_from_file(&bundle.blitzar_handle_path().to_string_lossy());
+    Ok((bundle.public_parameters, blitzar_handle))
+}
+
+/// Load a prover archive, keeping its blitzar handle on disk until the bundle is dropped.
+///
+/// Build prover setups from it with [`LoadedBundle::prover_setup`].
+pub fn load_bundle(archive_path: &Path) -> Result<LoadedBundle, ParamGenError> {
+    let archive_error = |source| ParamGenError::Archive {
+        path: archive_path.to_path_buf(),
+        source,
+    };
+    let mut archive = open_archive(archive_path).map_err(archive_error)?;
+
+    let mut public_parameters = None;
+    let mut handle_dir = None;
+    for entry in archive.entries().map_err(archive_error)? {
+        let mut entry = entry.map_err(archive_error)?;
+        let name = entry.path().map_err(archive_error)?.into_owned();
+        if name == Path::new(PUBLIC_PARAMETERS_FILE) {
+            public_parameters = Some(read_public_parameters(&mut entry, name)?);
+        } else if name == Path::new(BLITZAR_HANDLE_FILE) {
+            let dir = TempDir::new("dory-load").map_err(archive_error)?;
+            let mut handle_file =
+                File::create(dir.join(BLITZAR_HANDLE_FILE)).map_err(archive_error)?;
+            io::copy(&mut entry, &mut handle_file).map_err(archive_error)?;
+            handle_dir = Some(dir);
+        }
+    }
+
+    match (public_parameters, handle_dir) {
+        (Some(public_parameters), Some(handle_dir)) => Ok(LoadedBundle {
+            public_parameters,
+            handle_dir,
+        }),
+        (None, _) => Err(archive_error(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("the archive has no {}", PUBLIC_PARAMETERS_FILE),
+        ))),
+        (_, None) => Err(archive_error(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("the archive has no {}", BLITZAR_HANDLE_FILE),
+        ))),
+    }
+}
+
+/// Load the public parameters and generation metadata from a prover archive, skipping the
+/// blitzar handle.
+///
+/// The metadata is `None` for archives written before it was recorded.
+pub fn load_public_parameters(
+    archive_path: &Path,
+) -> Result<(PublicParameters, Option<BundleMetadata>), ParamGenError> {
+    let archive_error = |source| ParamGenError::Archive {
+        path: archive_path.to_path_buf(),
+        source,
+    };
+    let mut archive = open_archive(archive_path).map_err(archive_error)?;
+
+    let mut public_parameters = None;
+    let mut metadata = None;
+    for entry in archive.entries().map_err(archive_error)? {
+        let mut entry = entry.map_err(archive_error)?;
+        let name = entry.path().map_err(archive_error)?.into_owned();
+        if name == Path::new(PUBLIC_PARAMETERS_FILE) {
+            public_parameters = Some(read_public_parameters(&mut entry, name)?);
+        } else if name == Path::new(METADATA_FILE) {
+            let parsed =
+                serde_json::from_reader(&mut entry).map_err(|e| ParamGenError::Serialization {
+                    path: name,
+                    source: e.into(),
+                })?;
+            metadata = Some(parsed);
+        }
+    }
+
+    let public_parameters = public_parameters.ok_or_else(|| {
+        archive_error(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("the archive has no {}", PUBLIC_PARAMETERS_FILE),
+        ))
+    })?;
+    Ok((public_parameters, metadata))
+}
+
+// The directory next to `archive_name` that its members are kept in: the archive's name
+// without the format's extension
+fn intermediates_dir_name(archive_name: &str, opts: &BundleOptions) -> String {
+    match archive_name.strip_suffix(opts.compression.format.extension()) {
+        Some(stem) if !stem.is_empty() => stem.to_string(),
+        _ => format!("{}.d", archive_name),
+    }
+}
+
+// With `opts.keep_intermediates`, have `archive` also write its members into their staged
+// directory
+fn keep_members(
+    archive: &mut ArchiveWriter,
+    staging: &TempDir,
+    archive_name: &str,
+    opts: &BundleOptions,
+) -> Result<(), ParamGenError> {
+    if !opts.keep_intermediates {
+        return Ok(());
+    }
+    let dir = staging.join(&intermediates_dir_name(archive_name, opts));
+    fs::create_dir(&dir).map_err(|source| ParamGenError::OutputDir {
+        path: dir.clone(),
+        source,
+    })?;
+    archive.copy_members_into(&dir);
+    Ok(())
+}
+
+// Move every file in `from` into `to`, creating it if needed and replacing files of the same
+// name like the archives themselves are replaced
+fn move_files(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        fs::rename(entry.path(), to.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+// Deserialize and validate the public parameters from the archive entry `name`
+fn read_public_parameters(
+    entry: &mut impl io::Read,
+    name: PathBuf,
+) -> Result<PublicParameters, ParamGenError> {
+    PublicParameters::deserialize_with_mode(BufReader::new(entry), Compress::No, Validate::Yes)
+        .map_err(|e| ParamGenError::Serialization {
+            path: name,
+            source: io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+        })
+}
+
+// Append the metadata, a manifest of every member (and its signature, if a key is given) and
+// finish the archive at `archive_path`, returning that path and the archive's digest
+fn seal(
+    mut archive: ArchiveWriter,
+    archive_path: &Path,
+    mut members: Vec<ManifestEntry>,
+    metadata_json: &[u8],
+    nu: usize,
+    opts: &BundleOptions,
+    progress: &dyn Progress,
+) -> Result<(PathBuf, ManifestEntry), ParamGenError> {
+    let archive_error = |source| ParamGenError::Archive {
+        path: archive_path.to_path_buf(),
+        source,
+    };
+    // The manifest covers the metadata, so hash it before anything is written
+    members.push(ManifestEntry {
+        name: METADATA_FILE.to_string(),
+        size: metadata_json.len() as u64,
+        sha256: manifest::sha256_hex(metadata_json),
+    });
+    let manifest = match &opts.hash_to_curve_dst {
+        // The seed took no part in hashed generators, so it is not fingerprinted
+        Some(dst) => Manifest {
+            seed_fingerprint: None,
+            hash_to_curve_dst: Some(dst.clone()),
+            parent_archive: opts.parent_archive.clone(),
+            ..Manifest::from_members(nu, &opts.seed, members)
+        },
+        None => Manifest {
+            parent_archive: opts.parent_archive.clone(),
+            ..Manifest::from_members(nu, &opts.seed, members)
+        },
+    };
+    let manifest_json =
+        serde_json::to_vec_pretty(&manifest).map_err(|e| ParamGenError::Manifest(e.to_string()))?;
+    let signature = opts
+        .signing_key
+        .as_ref()
+        .map(|key| manifest::sign(key, &manifest_json));
+
+    let mut trailer = vec![
+        (METADATA_FILE, metadata_json),
+        (MANIFEST_FILE, &manifest_json),
+    ];
+    if let Some(signature) = &signature {
+        trailer.push((SIGNATURE_FILE, signature.as_bytes()));
+    }
+    progress.start(
+        Phase::Archive,
+        Some(trailer.iter().map(|(_, bytes)| bytes.len() as u64).sum()),
+    );
+    for (name, bytes) in trailer {
+        archive
+            .append(name, bytes.len() as u64, bytes)
+            .map_err(archive_error)?;
+    }
+    let digest = archive.finish().map_err(archive_error)?;
+    progress.finish();
+    Ok((archive_path.to_path_buf(), digest))
+}
+
+// Run a generation step, turning a panic inside the proof-of-sql or blitzar code into an error
+fn catch_generation<T>(step: impl FnOnce() -> T) -> Result<T, ParamGenError> {
+    panic::catch_unwind(AssertUnwindSafe(step)).map_err(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "unknown panic".to_string());
+        ParamGenError::Generation(message)
+    })
+}
@@ -0,0 +1,391 @@
+//! A multi-party ceremony that rerandomizes the Dory generators so no single party knows any
+//! relation between them.
+//!
+//! Each contribution multiplies every generator by its own secret scalar and proves knowledge of
+//! those scalars with a Schnorr proof per generator. As long as one participant discards their
+//! scalars, nobody can know discrete-log relations between the final generators. Participants
+//! hand the transcript directory to each other; it holds every intermediate state so the whole
+//! chain can be checked from the initial, seed-derived state.
+
+use crate::{
+    atomic::save_atomically, manifest::sha256_file, progress::NoProgress,
+    raw_parameters::RawParameters, seed::Seed,
+};
+use ark_bls12_381::{Fr, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::{AffineRepr, CurveGroup, VariableBaseMSM};
+use ark_ff::{PrimeField, UniformRand};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use proof_of_sql::proof_primitive::dory::PublicParameters;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// File name of the transcript index inside a ceremony directory.
+pub const TRANSCRIPT_FILE: &str = "transcript.json";
+
+// Separates ceremony challenges from any other use of the same hash
+const DOMAIN: &[u8] = b"sxt-dory-ceremony-v1";
+
+/// A file in the ceremony directory and its SHA-256.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileDigest {
+    pub file: String,
+    pub sha256: String,
+}
+
+/// One participant's rerandomization of the previous state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Contribution {
+    pub index: usize,
+    pub participant: String,
+    /// The state after this contribution, in the `public_parameters.bin` format
+    pub state: FileDigest,
+    /// The proof that `state` rerandomizes the previous state
+    pub proof: FileDigest,
+}
+
+/// The index of a ceremony directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    pub nu: usize,
+    /// Hex seed the initial state was generated from
+    pub initial_seed: String,
+    pub initial_state: FileDigest,
+    pub contributions: Vec<Contribution>,
+}
+
+impl Transcript {
+    /// Read the transcript index from a ceremony directory.
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let json = fs::read(dir.join(TRANSCRIPT_FILE))
+            .map_err(|e| format!("failed to read {}: {}", TRANSCRIPT_FILE, e))?;
+        serde_json::from_slice(&json).map_err(|e| format!("failed to parse transcript: {}", e))
+    }
+
+    fn save(&self, dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        save_atomically(&dir.join(TRANSCRIPT_FILE), |writer| writer.write_all(&json))
+            .map_err(|e| format!("failed to write {}: {}", TRANSCRIPT_FILE, e))
+    }
+
+    /// The most recent state in the chain.
+    pub fn latest_state(&self) -> &FileDigest {
+        self.contributions
+            .last()
+            .map_or(&self.initial_state, |c| &c.state)
+    }
+}
+
+// Schnorr proofs of knowledge of the scalar taking each old generator to its new value
+#[derive(CanonicalSerialize, CanonicalDeserialize)]
+struct ContributionProof {
+    g1_commitments: Vec<G1Affine>,
+    g1_responses: Vec<Fr>,
+    g2_commitments: Vec<G2Affine>,
+    g2_responses: Vec<Fr>,
+}
+
+/// Start a ceremony in `dir` from parameters generated for `nu` from `seed`.
+pub fn init(dir: &Path, nu: usize, seed: &Seed) -> Result<Transcript, String> {
+    if dir.join(TRANSCRIPT_FILE).exists() {
+        return Err(format!("{} already holds a ceremony", dir.display()));
+    }
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+
+    let state = RawParameters::rand(nu, &mut seed.rng(), &NoProgress);
+    let transcript = Transcript {
+        nu,
+        initial_seed: seed.to_hex(),
+        initial_state: save_state(dir, "state-0000.bin", &state)?,
+        contributions: Vec::new(),
+    };
+    transcript.save(dir)?;
+    Ok(transcript)
+}
+
+/// Rerandomize the latest state in `dir` with fresh OS entropy and append the result.
+pub fn contribute(dir: &Path, participant: &str) -> Result<Contribution, String> {
+    let mut transcript = Transcript::load(dir)?;
+    let previous = transcript.latest_state().clone();
+    let old_state = load_state(dir, &previous)?;
+    let mut rng = ChaCha20Rng::from_entropy();
+
+    let (g1_old, g2_old) = split(&old_state);
+    let (g1_new, g1_commitments, g1_secrets, g1_nonces) =
+        rerandomize::<G1Projective>(&g1_old, &mut rng);
+    let (g2_new, g2_commitments, g2_secrets, g2_nonces) =
+        rerandomize::<G2Projective>(&g2_old, &mut rng);
+
+    let index = transcript.contributions.len() + 1;
+    let state = save_state(
+        dir,
+        &format!("state-{:04}.bin", index),
+        &join(g1_new, g2_new),
+    )?;
+
+    let challenge = challenge(
+        participant,
+        &previous,
+        &state,
+        &g1_commitments,
+        &g2_commitments,
+    )?;
+    let respond = |secrets: Vec<Fr>, nonces: Vec<Fr>| -> Vec<Fr> {
+        nonces
+            .into_iter()
+            .zip(secrets)
+            .map(|(k, r)| k + challenge * r)
+            .collect()
+    };
+    let proof = ContributionProof {
+        g1_commitments,
+        g1_responses: respond(g1_secrets, g1_nonces),
+        g2_commitments,
+        g2_responses: respond(g2_secrets, g2_nonces),
+    };
+    let proof_file = format!("proof-{:04}.bin", index);
+    let proof_path = dir.join(&proof_file);
+    write_serialized(&proof_path, &proof)?;
+
+    let contribution = Contribution {
+        index,
+        participant: participant.to_string(),
+        state,
+        proof: digest_of(dir, &proof_file)?,
+    };
+    transcript.contributions.push(contribution.clone());
+    transcript.save(dir)?;
+    Ok(contribution)
+}
+
+/// Check the initial state against its seed, then every file digest and every contribution proof
+/// in `dir`, returning the transcript.
+pub fn verify(dir: &Path) -> Result<Transcript, String> {
+    let transcript = Transcript::load(dir)?;
+    let mut previous = transcript.initial_state.clone();
+    let mut old_state = load_state(dir, &previous)?;
+    if old_state.nu() != transcript.nu {
+        return Err(format!(
+            "initial state is for nu = {}, transcript records {}",
+            old_state.nu(),
+            transcript.nu
+        ));
+    }
+    // The whole chain rests on the initial state being the one the recorded seed produces
+    let seed = Seed::from_hex(&transcript.initial_seed)?;
+    if RawParameters::rand(transcript.nu, &mut seed.rng(), &NoProgress) != old_state {
+        return Err(format!(
+            "initial state does not match the parameters generated from seed {}",
+            transcript.initial_seed
+        ));
+    }
+    let mut rng = ChaCha20Rng::from_entropy();
+
+    for contribution in &transcript.contributions {
+        let context = |e: String| {
+            format!(
+                "contribution {} by {}: {}",
+                contribution.index, contribution.participant, e
+            )
+        };
+        let new_state = load_state(dir, &contribution.state).map_err(context)?;
+        check_digest(dir, &contribution.proof).map_err(context)?;
+        let proof: ContributionProof =
+            read_serialized(&dir.join(&contribution.proof.file)).map_err(context)?;
+
+        let challenge = challenge(
+            &contribution.participant,
+            &previous,
+            &contribution.state,
+            &proof.g1_commitments,
+            &proof.g2_commitments,
+        )?;
+        let ((g1_old, g2_old), (g1_new, g2_new)) = (split(&old_state), split(&new_state));
+        check_rerandomization::<G1Projective>(
+            &g1_old,
+            &g1_new,
+            &proof.g1_commitments,
+            &proof.g1_responses,
+            challenge,
+            &mut rng,
+        )
+        .map_err(|e| context(format!("G1: {}", e)))?;
+        check_rerandomization::<G2Projective>(
+            &g2_old,
+            &g2_new,
+            &proof.g2_commitments,
+            &proof.g2_responses,
+            challenge,
+            &mut rng,
+        )
+        .map_err(|e| context(format!("G2: {}", e)))?;
+
+        previous = contribution.state.clone();
+        old_state = new_state;
+    }
+    Ok(transcript)
+}
+
+/// Verify the ceremony in `dir` and return its final parameters.
+pub fn finalize(dir: &Path) -> Result<(PublicParameters, Transcript), String> {
+    let transcript = verify(dir)?;
+    if transcript.contributions.is_empty() {
+        return Err("the ceremony has no contributions yet".to_string());
+    }
+    let public_parameters = load_state(dir, transcript.latest_state())?
+        .to_public_parameters()
+        .map_err(|e| format!("failed to build public parameters: {}", e))?;
+    Ok((public_parameters, transcript))
+}
+
+// All G1 elements (`Γ_1` then `H_1`) and all G2 elements (`Γ_2`, `H_2`, `Γ_2,fin`)
+fn split(state: &RawParameters) -> (Vec<G1Affine>, Vec<G2Affine>) {
+    let mut g1 = state.gamma_1.clone();
+    g1.push(state.h_1);
+    let mut g2 = state.gamma_2.clone();
+    g2.extend([state.h_2, state.gamma_2_fin]);
+    (g1, g2)
+}
+
+// Inverse of `split`
+fn join(mut g1: Vec<G1Affine>, mut g2: Vec<G2Affine>) -> RawParameters {
+    let h_1 = g1.pop().expect("split always appends H_1");
+    let gamma_2_fin = g2.pop().expect("split always appends Γ_2,fin");
+    let h_2 = g2.pop().expect("split always appends H_2");
+    RawParameters {
+        gamma_1: g1,
+        gamma_2: g2,
+        h_1,
+        h_2,
+        gamma_2_fin,
+    }
+}
+
+// Multiply each element by a fresh secret, returning the new elements, the Schnorr
+// commitments, the secrets and the commitment nonces
+#[allow(clippy::type_complexity)]
+fn rerandomize<G: CurveGroup<ScalarField = Fr>>(
+    old: &[G::Affine],
+    rng: &mut impl Rng,
+) -> (Vec<G::Affine>, Vec<G::Affine>, Vec<Fr>, Vec<Fr>) {
+    let secrets: Vec<Fr> = (0..old.len()).map(|_| Fr::rand(rng)).collect();
+    let nonces: Vec<Fr> = (0..old.len()).map(|_| Fr::rand(rng)).collect();
+    let scale = |scalars: &[Fr]| {
+        let points: Vec<G> = old
+            .par_iter()
+            .zip(scalars)
+            .map(|(point, scalar)| *point * scalar)
+            .collect();
+        G::normalize_batch(&points)
+    };
+    (scale(&secrets), scale(&nonces), secrets, nonces)
+}
+
+// Batch-check `responses[i] * old[i] == commitments[i] + challenge * new[i]` for all `i`
+// using a random linear combination
+fn check_rerandomization<G: CurveGroup<ScalarField = Fr> + VariableBaseMSM>(
+    old: &[G::Affine],
+    new: &[G::Affine],
+    commitments: &[G::Affine],
+    responses: &[Fr],
+    challenge: Fr,
+    rng: &mut impl Rng,
+) -> Result<(), String> {
+    let n = old.len();
+    if new.len() != n || commitments.len() != n || responses.len() != n {
+        return Err("proof length does not match the number of generators".to_string());
+    }
+    if let Some(i) = new.iter().position(|p| p.is_zero()) {
+        return Err(format!("generator {} was set to the identity", i));
+    }
+    let weights: Vec<Fr> = (0..n).map(|_| Fr::rand(rng)).collect();
+    let weighted =
+        |scalars: &[Fr]| -> Vec<Fr> { weights.iter().zip(scalars).map(|(w, s)| *w * s).collect() };
+    let lhs = G::msm_unchecked(old, &weighted(responses));
+    let challenge_weights: Vec<Fr> = weights.iter().map(|w| *w * challenge).collect();
+    let rhs = G::msm_unchecked(commitments, &weights) + G::msm_unchecked(new, &challenge_weights);
+    if lhs != rhs {
+        return Err("the proof does not show a rerandomization of the previous state".to_string());
+    }
+    Ok(())
+}
+
+// Fiat-Shamir challenge binding the participant, both states and all commitments
+fn challenge(
+    participant: &str,
+    previous: &FileDigest,
+    state: &FileDigest,
+    g1_commitments: &[G1Affine],
+    g2_commitments: &[G2Affine],
+) -> Result<Fr, String> {
+    let mut context = Context::new(&SHA256);
+    context.update(DOMAIN);
+    context.update(&(participant.len() as u64).to_le_bytes());
+    context.update(participant.as_bytes());
+    context.update(previous.sha256.as_bytes());
+    context.update(state.sha256.as_bytes());
+    let mut commitments = Vec::new();
+    g1_commitments
+        .serialize_with_mode(&mut commitments, Compress::No)
+        .and_then(|()| g2_commitments.serialize_with_mode(&mut commitments, Compress::No))
+        .map_err(|e| e.to_string())?;
+    context.update(&commitments);
+    Ok(Fr::from_le_bytes_mod_order(context.finish().as_ref()))
+}
+
+fn save_state(dir: &Path, file: &str, state: &RawParameters) -> Result<FileDigest, String> {
+    state
+        .save_to_file(&dir.join(file))
+        .map_err(|e| format!("failed to write {}: {}", file, e))?;
+    digest_of(dir, file)
+}
+
+fn load_state(dir: &Path, state: &FileDigest) -> Result<RawParameters, String> {
+    check_digest(dir, state)?;
+    RawParameters::load_from_file(&dir.join(&state.file))
+        .map_err(|e| format!("failed to load {}: {}", state.file, e))
+}
+
+fn digest_of(dir: &Path, file: &str) -> Result<FileDigest, String> {
+    let (_, sha256) =
+        sha256_file(&dir.join(file)).map_err(|e| format!("failed to hash {}: {}", file, e))?;
+    Ok(FileDigest {
+        file: file.to_string(),
+        sha256,
+    })
+}
+
+fn check_digest(dir: &Path, expected: &FileDigest) -> Result<(), String> {
+    let actual = digest_of(dir, &expected.file)?;
+    if actual.sha256 != expected.sha256 {
+        return Err(format!(
+            "{} has SHA-256 {}, transcript records {}",
+            expected.file, actual.sha256, expected.sha256
+        ));
+    }
+    Ok(())
+}
+
+fn write_serialized(path: &Path, value: &impl CanonicalSerialize) -> Result<(), String> {
+    let file =
+        File::create(path).map_err(|e| format!("failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    value
+        .serialize_with_mode(&mut writer, Compress::No)
+        .map_err(|e| e.to_string())
+        .and_then(|()| writer.flush().map_err(|e| e.to_string()))
+        .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+fn read_serialized<T: CanonicalDeserialize>(path: &Path) -> Result<T, String> {
+    let file = File::open(path).map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+    T::deserialize_with_mode(BufReader::new(file), Compress::No, Validate::Yes)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+}
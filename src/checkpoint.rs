@@ -0,0 +1,350 @@
+//! Checkpoints that let an interrupted generation run pick up where it stopped.
+//!
+//! A work directory holds `checkpoint.json` next to the artifacts of every finished phase: the
+//! generated public parameters, the blitzar handle for each `nu` packaged and the digests of
+//! the archives already written. An artifact is only recorded once it is fully on disk, and a
+//! resumed run hashes it again before trusting it, so a crash can at worst repeat a phase. An
+//! artifact still being written when the process is interrupted is removed.
+
+use crate::{
+    archive::PUBLIC_PARAMETERS_FILE,
+    atomic::save_atomically,
+    bundle::{write_blitzar_handle, BlitzarHandle, BundleOutput},
+    interrupt,
+    manifest::{sha256_file, ManifestEntry},
+    metadata::BundleMetadata,
+    progress::{CountingWriter, Phase, Progress},
+    sizes::{nu_of, ArtifactSizes},
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use proof_of_sql::proof_primitive::dory::{ProverSetup, PublicParameters};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// File name of the checkpoint index inside a work directory.
+pub const CHECKPOINT_FILE: &str = "checkpoint.json";
+
+/// The phases of a run that have finished, with the digest of what each one produced.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// The run being checkpointed, which a resumed run must repeat exactly
+    pub metadata: BundleMetadata,
+    /// The generated public parameters, recorded once they are saved in the work directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_parameters: Option<ManifestEntry>,
+    /// One entry per `nu` whose packaging has started
+    #[serde(default)]
+    pub tiers: Vec<TierCheckpoint>,
+}
+
+/// The packaging progress for one `nu`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TierCheckpoint {
+    pub nu: usize,
+    /// The blitzar handle, recorded once the prover setup is built and its handle written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blitzar_handle: Option<ManifestEntry>,
+    /// The finished archives, named by their paths
+    #[serde(default)]
+    pub archives: Vec<ManifestEntry>,
+}
+
+/// A work directory and the checkpoint it holds.
+#[derive(Debug)]
+pub struct WorkDir {
+    dir: PathBuf,
+    checkpoint: Checkpoint,
+}
+
+impl WorkDir {
+    /// Start checkpointing the run described by `metadata` in `dir`, which must not already
+    /// hold a checkpoint.
+    pub fn create(dir: &Path, metadata: BundleMetadata) -> Result<Self, String> {
+        if dir.join(CHECKPOINT_FILE).exists() {
+            return Err(format!(
+                "{} already holds a checkpoint; pass it to --resume instead",
+                dir.display()
+            ));
+        }
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        let work_dir = Self {
+            dir: dir.to_path_buf(),
+            checkpoint: Checkpoint {
+                metadata,
+                public_parameters: None,
+                tiers: Vec::new(),
+            },
+        };
+        work_dir.save()?;
+        Ok(work_dir)
+    }
+
+    /// Reopen the checkpoint in `dir`, refusing it unless it was written for the same run.
+    pub fn resume(dir: &Path, metadata: &BundleMetadata) -> Result<Self, String> {
+        let json = fs::read(dir.join(CHECKPOINT_FILE)).map_err(|e| {
+            format!(
+                "failed to read {} in {}: {}",
+                CHECKPOINT_FILE,
+                dir.display(),
+                e
+            )
+        })?;
+        let checkpoint: Checkpoint = serde_json::from_slice(&json)
+            .map_err(|e| format!("failed to parse {}: {}", CHECKPOINT_FILE, e))?;
+        let recorded = &checkpoint.metadata;
+        if recorded.nu != metadata.nu
+            || recorded.seed != metadata.seed
+            || recorded.hash_to_curve_dst != metadata.hash_to_curve_dst
+        {
+            return Err(format!(
+                "{} was written for nu = {} from {}, not this run",
+                dir.display(),
+                recorded.nu,
+                recorded.derivation()
+            ));
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            checkpoint,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+
+    /// Load the checkpointed public parameters, or `None` if generation never finished.
+    pub fn load_public_parameters(&self) -> Result<Option<PublicParameters>, String> {
+        let Some(entry) = &self.checkpoint.public_parameters else {
+            return Ok(None);
+        };
+        let path = self.check(entry)?;
+        let file =
+            File::open(&path).map_err(|e| format!("failed to open {}: {}", entry.name, e))?;
+        // The digest already proves these are the bytes that were generated, so checking every
+        // element again would only repeat a long validation
+        PublicParameters::deserialize_with_mode(BufReader::new(file), Compress::No, Validate::No)
+            .map(Some)
+            .map_err(|e| format!("failed to read {}: {}", entry.name, e))
+    }
+
+    /// Save freshly generated public parameters and record them.
+    pub fn save_public_parameters(
+        &mut self,
+        public_parameters: &PublicParameters,
+        progress: &dyn Progress,
+    ) -> Result<(), String> {
+        let path = self.dir.join(PUBLIC_PARAMETERS_FILE);
+        interrupt::register(&path);
+        let result = self.write_public_parameters(&path, public_parameters, progress);
+        interrupt::unregister(&path);
+        result
+    }
+
+    // Write, hash and record the public parameters at `path`
+    fn write_public_parameters(
+        &mut self,
+        path: &Path,
+        public_parameters: &PublicParameters,
+        progress: &dyn Progress,
+    ) -> Result<(), String> {
+        let size = ArtifactSizes::for_nu(nu_of(public_parameters)).public_parameters;
+        progress.start(Phase::PublicParameters, Some(size));
+        let write = || -> std::io::Result<()> {
+            let file = File::create(path)?;
+            let mut writer = BufWriter::with_capacity(
+                1 << 20,
+                CountingWriter::new(&file, |n, _| progress.advance(n)),
+            );
+            public_parameters
+                .serialize_with_mode(&mut writer, Compress::No)
+                .map_err(|e| std::io::Error::other(e.to_string()))?;
+            writer.flush()?;
+            drop(writer);
+            file.sync_all()
+        };
+        write().map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        progress.finish();
+        self.checkpoint.public_parameters = Some(self.hash(PUBLIC_PARAMETERS_FILE)?);
+        self.save()
+    }
+
+    /// Rebuild the prover setup for `public_parameters` from its checkpointed blitzar handle,
+    /// or `None` if the handle for that `nu` was never written.
+    pub fn load_prover_setup<'a>(
+        &self,
+        public_parameters: &'a PublicParameters,
+    ) -> Result<Option<ProverSetup<'a>>, String> {
+        let Some(path) = self.blitzar_handle_path(nu_of(public_parameters))? else {
+            return Ok(None);
+        };
+        let blitzar_handle = BlitzarHandle::new_from_file(&path.to_string_lossy());
+        Ok(Some(
+            ProverSetup::from_public_parameters_and_blitzar_handle(
+                public_parameters,
+                blitzar_handle,
+            ),
+        ))
+    }
+
+    /// The checkpointed blitzar handle for `nu` after checking its digest, or `None` if it was
+    /// never written.
+    pub fn blitzar_handle_path(&self, nu: usize) -> Result<Option<PathBuf>, String> {
+        self.tier(nu)
+            .and_then(|tier| tier.blitzar_handle.as_ref())
+            .map(|entry| self.check(entry))
+            .transpose()
+    }
+
+    /// Save the blitzar handle of a freshly built prover setup for `nu` and record it.
+    pub fn save_blitzar_handle(
+        &mut self,
+        nu: usize,
+        blitzar_handle: &BlitzarHandle,
+        progress: &dyn Progress,
+    ) -> Result<(), String> {
+        let name = format!("blitzar_handle-nu{}.bin", nu);
+        let path = self.dir.join(&name);
+        interrupt::register(&path);
+        let result = self.write_blitzar_handle(nu, &name, blitzar_handle, progress);
+        interrupt::unregister(&path);
+        result
+    }
+
+    // Write, check and record the blitzar handle `name`
+    fn write_blitzar_handle(
+        &mut self,
+        nu: usize,
+        name: &str,
+        blitzar_handle: &BlitzarHandle,
+        progress: &dyn Progress,
+    ) -> Result<(), String> {
+        let path = self.dir.join(name);
+        progress.start(Phase::BlitzarHandle, None);
+        let written = write_blitzar_handle(
+            blitzar_handle,
+            &path,
+            ArtifactSizes::for_nu(nu).blitzar_handle,
+        );
+        progress.finish();
+        written.map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        self.tier_mut(nu).blitzar_handle = Some(self.hash(name)?);
+        self.save()
+    }
+
+    /// Whether `archives` were already written for `nu` and are all still in place and
+    /// unchanged.
+    ///
+    /// Unlike the artifacts inside the work directory, archives are outputs that may since
+    /// have been moved or replaced, so a mismatch means they are written again rather than an
+    /// error.
+    pub fn archives_finished(&self, nu: usize, archives: &[PathBuf]) -> bool {
+        self.finished_archives(nu, archives).is_some()
+    }
+
+    /// The digests of `archives`, named by their file names, if they were already written for
+    /// `nu` and are all still in place and unchanged.
+    pub fn finished_archives(&self, nu: usize, archives: &[PathBuf]) -> Option<Vec<ManifestEntry>> {
+        let tier = self.tier(nu)?;
+        let unchanged = tier.archives.len() == archives.len()
+            && tier.archives.iter().zip(archives).all(|(entry, path)| {
+                Path::new(&entry.name) == path
+                    && sha256_file(path)
+                        .is_ok_and(|(size, sha256)| size == entry.size && sha256 == entry.sha256)
+            });
+        unchanged.then(|| {
+            tier.archives
+                .iter()
+                .zip(archives)
+                .map(|(entry, path)| ManifestEntry {
+                    name: path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    ..entry.clone()
+                })
+                .collect()
+        })
+    }
+
+    /// Record the finished archives of a bundle written for `nu`, using the digests taken while
+    /// they were written.
+    pub fn record_archives(&mut self, nu: usize, output: &BundleOutput) -> Result<(), String> {
+        self.tier_mut(nu).archives = output
+            .archives
+            .iter()
+            .zip(&output.digests)
+            .map(|(path, digest)| ManifestEntry {
+                name: path.to_string_lossy().into_owned(),
+                ..digest.clone()
+            })
+            .collect();
+        self.save()
+    }
+
+    fn tier(&self, nu: usize) -> Option<&TierCheckpoint> {
+        self.checkpoint.tiers.iter().find(|tier| tier.nu == nu)
+    }
+
+    fn tier_mut(&mut self, nu: usize) -> &mut TierCheckpoint {
+        let tiers = &mut self.checkpoint.tiers;
+        let i = match tiers.iter().position(|tier| tier.nu == nu) {
+            Some(i) => i,
+            None => {
+                tiers.push(TierCheckpoint {
+                    nu,
+                    blitzar_handle: None,
+                    archives: Vec::new(),
+                });
+                tiers.len() - 1
+            }
+        };
+        &mut tiers[i]
+    }
+
+    // Hash the artifact `name` inside the work directory
+    fn hash(&self, name: &str) -> Result<ManifestEntry, String> {
+        let (size, sha256) = sha256_file(&self.dir.join(name))
+            .map_err(|e| format!("failed to hash {}: {}", name, e))?;
+        Ok(ManifestEntry {
+            name: name.to_string(),
+            size,
+            sha256,
+        })
+    }
+
+    // Check a recorded artifact against its digest, returning its path
+    fn check(&self, entry: &ManifestEntry) -> Result<PathBuf, String> {
+        let actual = self.hash(&entry.name)?;
+        if actual.size != entry.size || actual.sha256 != entry.sha256 {
+            return Err(format!(
+                "{} in {} no longer matches its checkpoint ({} bytes with SHA-256 {}, recorded \
+                 {} bytes with SHA-256 {}); remove the work directory to start over",
+                entry.name,
+                self.dir.display(),
+                actual.size,
+                actual.sha256,
+                entry.size,
+                entry.sha256
+            ));
+        }
+        Ok(self.dir.join(&entry.name))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(&self.checkpoint).map_err(|e| e.to_string())?;
+        save_atomically(&self.dir.join(CHECKPOINT_FILE), |writer| {
+            writer.write_all(&json)
+        })
+        .map_err(|e| format!("failed to write {}: {}", CHECKPOINT_FILE, e))
+    }
+}
@@ -0,0 +1,319 @@
+//! Compare two prover bundles by their contents rather than by archive hashes.
+//!
+//! Archive hashes change with compression settings, tar metadata and member order, so the
+//! members are decoded instead. Both `public_parameters.bin` members are streamed side by side:
+//! each holds `Γ_1`, then `Γ_2`, then the fixed generators, so the elements both sets share can
+//! be compared in one sequential pass without loading either set. The blitzar handles are then
+//! compared one precomputed partition at a time.
+
+use crate::{
+    archive::{open_archive, BLITZAR_HANDLE_FILE, PUBLIC_PARAMETERS_FILE},
+    progress::{CountingReader, Phase, Progress},
+    sizes::{blitzar_partition_size, blitzar_partitions, parse_nu_header, BLITZAR_PARTITION_WIDTH},
+};
+use ark_bls12_381::{G1Affine, G2Affine};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+use serde::Serialize;
+use std::{
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+/// How the contents of two bundles relate.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Relation {
+    /// The same `nu` and every element equal
+    Equivalent,
+    /// The first bundle is the second truncated to a smaller `nu`
+    FirstIsPrefix,
+    /// The second bundle is the first truncated to a smaller `nu`
+    SecondIsPrefix,
+    /// An element both bundles hold differs
+    Unrelated,
+}
+
+impl Relation {
+    // The relation of two matching element sequences of `2^first_nu` and `2^second_nu` elements
+    fn of_matching(first_nu: usize, second_nu: usize) -> Self {
+        match first_nu.cmp(&second_nu) {
+            std::cmp::Ordering::Equal => Relation::Equivalent,
+            std::cmp::Ordering::Less => Relation::FirstIsPrefix,
+            std::cmp::Ordering::Greater => Relation::SecondIsPrefix,
+        }
+    }
+}
+
+/// The first generator that differs between two sets of public parameters.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementDifference {
+    /// `gamma_1`, `gamma_2`, `h_1`, `h_2` or `gamma_2_fin`
+    pub vector: &'static str,
+    /// Index within the vector, always 0 for the fixed generators
+    pub index: u64,
+    /// `G1` or `G2`
+    pub group: &'static str,
+}
+
+/// How two blitzar handles relate, partition by partition.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandleComparison {
+    pub first_partitions: u64,
+    pub second_partitions: u64,
+    pub relation: Relation,
+    /// The first partition both handles hold that differs; it covers `BLITZAR_PARTITION_WIDTH`
+    /// generators starting at `partition * BLITZAR_PARTITION_WIDTH`
+    pub first_difference: Option<u64>,
+}
+
+/// The result of [`diff_bundles`].
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BundleDiff {
+    pub first_nu: usize,
+    pub second_nu: usize,
+    /// How the public parameters relate
+    pub relation: Relation,
+    pub first_difference: Option<ElementDifference>,
+    pub blitzar_handles: HandleComparison,
+}
+
+impl BundleDiff {
+    /// Whether the handles tell the same story as the parameters; if not, at least one handle
+    /// was not built from the parameters next to it.
+    pub fn handles_agree(&self) -> bool {
+        self.blitzar_handles.relation == self.relation
+    }
+
+    /// Whether both the parameters and the handles are equivalent.
+    pub fn is_equivalent(&self) -> bool {
+        self.relation == Relation::Equivalent && self.handles_agree()
+    }
+}
+
+/// A failure to read or decode one of the two archives being compared.
+#[derive(Debug)]
+pub struct DiffError {
+    /// The archive that could not be read
+    pub path: PathBuf,
+    pub source: io::Error,
+}
+
+// An error reading or decoding the first (0) or second (1) of the two members being compared
+type SideError = (usize, io::Error);
+
+// Attribute an error to the first (0) or second (1) member
+fn on(side: usize) -> impl Fn(io::Error) -> SideError {
+    move |e| (side, e)
+}
+
+/// Compare the public parameters and blitzar handles of two prover archives.
+///
+/// Reading stops at the first difference, since the relation is settled by then.
+pub fn diff_bundles(
+    first: &Path,
+    second: &Path,
+    progress: &dyn Progress,
+) -> Result<BundleDiff, DiffError> {
+    let (first_nu, second_nu, first_difference) = with_members(
+        first,
+        second,
+        PUBLIC_PARAMETERS_FILE,
+        progress,
+        |a, b, _| compare_parameters(a, b),
+    )?;
+    let relation = match first_difference {
+        Some(_) => Relation::Unrelated,
+        None => Relation::of_matching(first_nu, second_nu),
+    };
+    let blitzar_handles = with_members(
+        first,
+        second,
+        BLITZAR_HANDLE_FILE,
+        progress,
+        |a, b, sizes| compare_handles(a, b, sizes, [first_nu, second_nu]),
+    )?;
+    Ok(BundleDiff {
+        first_nu,
+        second_nu,
+        relation,
+        first_difference,
+        blitzar_handles,
+    })
+}
+
+// Run `compare` on the member `name` of both archives, reporting the bytes read as one phase
+fn with_members<T>(
+    first: &Path,
+    second: &Path,
+    name: &str,
+    progress: &dyn Progress,
+    compare: impl FnOnce(&mut dyn Read, &mut dyn Read, [u64; 2]) -> Result<T, SideError>,
+) -> Result<T, DiffError> {
+    with_member(first, name, |a, first_size| {
+        with_member(second, name, |b, second_size| {
+            progress.start(Phase::Comparison, Some(first_size + second_size));
+            let mut a = BufReader::new(CountingReader::new(a, |n| progress.advance(n)));
+            let mut b = BufReader::new(CountingReader::new(b, |n| progress.advance(n)));
+            let result = compare(&mut a, &mut b, [first_size, second_size]);
+            progress.finish();
+            result.map_err(|(side, source)| DiffError {
+                path: [first, second][side].to_path_buf(),
+                source,
+            })
+        })
+    })
+}
+
+// Run `read` on the member `name` of the archive at `path` and its size
+fn with_member<T>(
+    path: &Path,
+    name: &str,
+    read: impl FnOnce(&mut dyn Read, u64) -> Result<T, DiffError>,
+) -> Result<T, DiffError> {
+    let error = |source| DiffError {
+        path: path.to_path_buf(),
+        source,
+    };
+    let mut archive = open_archive(path).map_err(error)?;
+    for entry in archive.entries().map_err(error)? {
+        let mut entry = entry.map_err(error)?;
+        if entry.path().map_err(error)? == Path::new(name) {
+            let size = entry.size();
+            return read(&mut entry, size);
+        }
+    }
+    Err(error(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} has no {}", path.display(), name),
+    )))
+}
+
+// Compare two serialized `PublicParameters`, returning both `nu` and the first difference
+fn compare_parameters(
+    a: &mut dyn Read,
+    b: &mut dyn Read,
+) -> Result<(usize, usize, Option<ElementDifference>), SideError> {
+    let (first_nu, second_nu) = (read_nu(a).map_err(on(0))?, read_nu(b).map_err(on(1))?);
+    let lengths = [1u64 << first_nu, 1u64 << second_nu];
+    // In serialization order: both vectors, then the fixed generators
+    let vectors = [
+        ("gamma_1", "G1", lengths),
+        ("gamma_2", "G2", lengths),
+        ("h_1", "G1", [1, 1]),
+        ("h_2", "G2", [1, 1]),
+        ("gamma_2_fin", "G2", [1, 1]),
+    ];
+    for (vector, group, lengths) in vectors {
+        let difference = match group {
+            "G1" => compare_vector::<G1Affine>(a, b, lengths, vector, group)?,
+            _ => compare_vector::<G2Affine>(a, b, lengths, vector, group)?,
+        };
+        if difference.is_some() {
+            return Ok((first_nu, second_nu, difference));
+        }
+    }
+    Ok((first_nu, second_nu, None))
+}
+
+// Compare the elements two vectors share, then skip past the longer one's extra elements
+fn compare_vector<T: CanonicalDeserialize + CanonicalSerialize + Default + PartialEq>(
+    a: &mut dyn Read,
+    b: &mut dyn Read,
+    lengths: [u64; 2],
+    vector: &'static str,
+    group: &'static str,
+) -> Result<Option<ElementDifference>, SideError> {
+    let read = |reader: &mut dyn Read| {
+        T::deserialize_with_mode(reader, Compress::No, Validate::No)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    };
+    for index in 0..lengths[0].min(lengths[1]) {
+        if read(a).map_err(on(0))? != read(b).map_err(on(1))? {
+            return Ok(Some(ElementDifference {
+                vector,
+                index,
+                group,
+            }));
+        }
+    }
+    let element_size = T::default().serialized_size(Compress::No) as u64;
+    let extra = lengths[0].abs_diff(lengths[1]) * element_size;
+    let (longer, side): (&mut dyn Read, _) = if lengths[0] > lengths[1] {
+        (a, 0)
+    } else {
+        (b, 1)
+    };
+    io::copy(&mut longer.take(extra), &mut io::sink()).map_err(on(side))?;
+    Ok(None)
+}
+
+// Read the `max_nu` a serialized `PublicParameters` starts with
+fn read_nu(reader: &mut dyn Read) -> io::Result<usize> {
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    parse_nu_header(&header).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", PUBLIC_PARAMETERS_FILE, e),
+        )
+    })
+}
+
+// Compare two handles one partition at a time.
+//
+// A partition depends only on its own generators, so a handle for a prefix of the parameters
+// is a prefix of the larger handle, except that blitzar pads a partial last partition. That
+// partition is left out when the two `nu` differ.
+fn compare_handles(
+    a: &mut dyn Read,
+    b: &mut dyn Read,
+    sizes: [u64; 2],
+    nus: [usize; 2],
+) -> Result<HandleComparison, SideError> {
+    let partitions = |size: u64| {
+        blitzar_partitions(size).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is {} bytes, not a whole number of partitions",
+                    BLITZAR_HANDLE_FILE, size
+                ),
+            )
+        })
+    };
+    let (first_partitions, second_partitions) = (
+        partitions(sizes[0]).map_err(on(0))?,
+        partitions(sizes[1]).map_err(on(1))?,
+    );
+    let smaller_nu = nus[0].min(nus[1]);
+    let mut comparable = first_partitions.min(second_partitions);
+    if nus[0] != nus[1] && !(1u64 << smaller_nu).is_multiple_of(BLITZAR_PARTITION_WIDTH) {
+        comparable = comparable.saturating_sub(1);
+    }
+
+    let partition_size = blitzar_partition_size() as usize;
+    let (mut buf_a, mut buf_b) = (vec![0u8; partition_size], vec![0u8; partition_size]);
+    let mut first_difference = None;
+    for partition in 0..comparable {
+        a.read_exact(&mut buf_a).map_err(on(0))?;
+        b.read_exact(&mut buf_b).map_err(on(1))?;
+        if buf_a != buf_b {
+            first_difference = Some(partition);
+            break;
+        }
+    }
+    // A handle with the wrong number of partitions for its parameters matches neither
+    let expected = |nu: usize| (1u64 << nu).div_ceil(BLITZAR_PARTITION_WIDTH);
+    let sized_for_nu =
+        first_partitions == expected(nus[0]) && second_partitions == expected(nus[1]);
+    let relation = match first_difference {
+        None if sized_for_nu => Relation::of_matching(nus[0], nus[1]),
+        _ => Relation::Unrelated,
+    };
+    Ok(HandleComparison {
+        first_partitions,
+        second_partitions,
+        relation,
+        first_difference,
+    })
+}
@@ -0,0 +1,86 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Everything that can stop a run, each with its own process exit code.
+///
+/// Exit code 2 is left to command-line usage errors, which clap reports before any of these.
+#[derive(Debug, Error)]
+pub enum ParamGenError {
+    /// An archive failed verification (exit code 1)
+    #[error("verification failed: {0}")]
+    Verification(String),
+
+    /// The RNG seed could not be resolved (exit code 13)
+    #[error("invalid seed: {0}")]
+    Seed(String),
+
+    /// The manifest signing key could not be loaded (exit code 14)
+    #[error("invalid signing key: {0}")]
+    SigningKey(String),
+
+    /// The pre-flight checks found too little disk space or memory (exit code 3)
+    #[error("insufficient resources: {}", .0.join("; "))]
+    InsufficientResources(Vec<String>),
+
+    /// The output or staging directory could not be created (exit code 4)
+    #[error("failed to prepare {}: {source}", path.display())]
+    OutputDir { path: PathBuf, source: io::Error },
+
+    /// Generating the public parameters or a setup failed (exit code 5)
+    #[error("parameter generation failed: {0}")]
+    Generation(String),
+
+    /// An artifact could not be serialized to disk (exit code 6)
+    #[error("failed to serialize {}: {source}", path.display())]
+    Serialization { path: PathBuf, source: io::Error },
+
+    /// blitzar did not produce a handle file (exit code 7)
+    #[error("failed to write the blitzar handle to {}: {reason}", path.display())]
+    BlitzarHandle { path: PathBuf, reason: String },
+
+    /// The archive manifest could not be built or signed (exit code 8)
+    #[error("failed to build manifest: {0}")]
+    Manifest(String),
+
+    /// Writing, compressing or moving an archive failed (exit code 9)
+    #[error("failed to write archive {}: {source}", path.display())]
+    Archive { path: PathBuf, source: io::Error },
+
+    /// Intermediate files could not be removed (exit code 10)
+    #[error("failed to clean up {}: {source}", path.display())]
+    Cleanup { path: PathBuf, source: io::Error },
+
+    /// A ceremony step failed or its transcript did not verify (exit code 11)
+    #[error("ceremony failed: {0}")]
+    Ceremony(String),
+
+    /// A checkpoint could not be written, or did not match the run or its artifacts (exit code 12)
+    #[error("checkpoint failed: {0}")]
+    Checkpoint(String),
+
+    /// The bundles compared by `diff` are not equivalent (exit code 15)
+    #[error("the bundles differ: {0}")]
+    Difference(String),
+}
+
+impl ParamGenError {
+    /// The process exit code for this error.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ParamGenError::Verification(_) => 1,
+            ParamGenError::InsufficientResources(_) => 3,
+            ParamGenError::OutputDir { .. } => 4,
+            ParamGenError::Generation(_) => 5,
+            ParamGenError::Serialization { .. } => 6,
+            ParamGenError::BlitzarHandle { .. } => 7,
+            ParamGenError::Manifest(_) => 8,
+            ParamGenError::Archive { .. } => 9,
+            ParamGenError::Cleanup { .. } => 10,
+            ParamGenError::Ceremony(_) => 11,
+            ParamGenError::Checkpoint(_) => 12,
+            ParamGenError::Seed(_) => 13,
+            ParamGenError::SigningKey(_) => 14,
+            ParamGenError::Difference(_) => 15,
+        }
+    }
+}
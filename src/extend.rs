@@ -0,0 +1,129 @@
+use crate::{
+    hash_to_curve::GeneratorHasher,
+    metadata::BundleMetadata,
+    progress::{Phase, Progress},
+    raw_parameters::{generator_count, RawParameters, PROGRESS_CHUNK},
+    seed::Seed,
+};
+use ark_bls12_381::{g1, g2, G1Affine, G2Affine};
+use ark_ec::short_weierstrass::{Affine, SWCurveConfig};
+use ark_ff::UniformRand;
+use rand::Rng;
+
+/// Extend parameters to a larger `nu` the same way they were originally generated.
+///
+/// The result equals a fresh generation at `to` from the seed or hash-to-curve tag recorded in
+/// `metadata`. Ceremony outputs cannot be extended, since their generators depend on secrets
+/// that no longer exist.
+pub fn extend(
+    parameters: &RawParameters,
+    metadata: &BundleMetadata,
+    to: usize,
+    progress: &dyn Progress,
+) -> Result<RawParameters, String> {
+    let from = parameters.nu();
+    if to <= from {
+        return Err(format!(
+            "the parameters already support nu = {}, cannot extend to {}",
+            from, to
+        ));
+    }
+    if let Some(source) = metadata.ceremony_source() {
+        return Err(format!(
+            "parameters from a {} cannot be extended; run a new ceremony instead",
+            source
+        ));
+    }
+    match &metadata.hash_to_curve_dst {
+        Some(dst) => extend_hash_to_curve(parameters, &GeneratorHasher::new(dst)?, to, progress),
+        None => extend_seeded(parameters, &metadata.seed()?, to, progress),
+    }
+}
+
+/// Extend seeded parameters by replaying the seed's RNG stream.
+///
+/// `PublicParameters::rand` draws `H_1`, `H_2` and `Γ_2,fin` and then each `(Γ_1[i], Γ_2[i])`
+/// pair in order. The fixed generators are redrawn to check that `parameters` came from `seed`,
+/// the existing pairs are skipped without the expensive cofactor clearing, and only the missing
+/// pairs are drawn in full. Skipped generators count towards `progress` like drawn ones.
+pub fn extend_seeded(
+    parameters: &RawParameters,
+    seed: &Seed,
+    to: usize,
+    progress: &dyn Progress,
+) -> Result<RawParameters, String> {
+    progress.start(Phase::Generators, Some(generator_count(to)));
+    let mut rng = seed.rng();
+    let fixed = (
+        G1Affine::rand(&mut rng),
+        G2Affine::rand(&mut rng),
+        G2Affine::rand(&mut rng),
+    );
+    if fixed != (parameters.h_1, parameters.h_2, parameters.gamma_2_fin) {
+        return Err(format!(
+            "the parameters were not generated from seed {}",
+            seed.to_hex()
+        ));
+    }
+    progress.advance(3);
+
+    let existing = parameters.gamma_1.len();
+    let mut extended = parameters.clone();
+    let mut done = 0;
+    while done < 1 << to {
+        let chunk = PROGRESS_CHUNK.min((1 << to) - done);
+        for i in done..done + chunk {
+            if i < existing {
+                skip_point::<g1::Config>(&mut rng);
+                skip_point::<g2::Config>(&mut rng);
+            } else {
+                extended.gamma_1.push(G1Affine::rand(&mut rng));
+                extended.gamma_2.push(G2Affine::rand(&mut rng));
+            }
+        }
+        progress.advance(2 * chunk as u64);
+        done += chunk;
+    }
+    progress.finish();
+    Ok(extended)
+}
+
+/// Extend hash-to-curve parameters by deriving only the missing indices.
+pub fn extend_hash_to_curve(
+    parameters: &RawParameters,
+    hasher: &GeneratorHasher,
+    to: usize,
+    progress: &dyn Progress,
+) -> Result<RawParameters, String> {
+    if (parameters.h_1, parameters.h_2, parameters.gamma_2_fin)
+        != (hasher.h_1(), hasher.h_2(), hasher.gamma_2_fin())
+    {
+        return Err(format!(
+            "the parameters were not derived from DST {:?}",
+            hasher.dst()
+        ));
+    }
+    let existing = parameters.gamma_1.len();
+    progress.start(
+        Phase::Generators,
+        Some(generator_count(to) - generator_count(parameters.nu())),
+    );
+    let missing = hasher.derive_range(existing..1 << to, progress);
+    progress.finish();
+    let mut extended = parameters.clone();
+    extended.gamma_1.extend(missing.0);
+    extended.gamma_2.extend(missing.1);
+    Ok(extended)
+}
+
+// Consume exactly the randomness `Affine::<P>::rand` would, mirroring its rejection loop in
+// ark-ec but without clearing the cofactor of the accepted point
+fn skip_point<P: SWCurveConfig>(rng: &mut impl Rng) {
+    loop {
+        let x = P::BaseField::rand(rng);
+        let _greatest: bool = rng.gen();
+        if Affine::<P>::get_ys_from_x_unchecked(x).is_some() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Block-parallel gzip compression.
+//!
+//! The input is cut into fixed-size blocks that are deflated independently on a thread pool,
+//! the way pigz does it. Every block but the last ends with a sync flush so the raw deflate
+//! streams concatenate into one, and the block CRCs are combined into the trailer. The result is
+//! a single ordinary gzip member that any decoder, including `flate2::read::GzDecoder`, reads
+//! back. Since the blocks do not depend on how many threads compressed them, the output bytes
+//! are the same for every thread count.
+
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use std::io::{self, Write};
+
+/// Uncompressed bytes deflated as one unit; larger blocks compress slightly better.
+pub const BLOCK_SIZE: usize = 1 << 20;
+
+/// A gzip encoder that deflates `BLOCK_SIZE` blocks in parallel.
+///
+/// Input is buffered until every thread has a block, so up to `threads * BLOCK_SIZE` bytes are
+/// held in memory. Call `finish` to write the final block and trailer.
+pub struct ParallelGzEncoder<W: Write> {
+    inner: W,
+    level: Compression,
+    pool: ThreadPool,
+    buffer: Vec<u8>,
+    batch_size: usize,
+    crc: Crc,
+}
+
+impl<W: Write> ParallelGzEncoder<W> {
+    /// Write the gzip header to `inner` and start a pool of `threads` for the blocks.
+    pub fn new(mut inner: W, level: u32, threads: usize) -> io::Result<Self> {
+        if level > 9 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("compression level must be 0 to 9, found {}", level),
+            ));
+        }
+        let threads = threads.max(1);
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("gzip-{}", i))
+            .build()
+            .map_err(io::Error::other)?;
+        inner.write_all(&header(level))?;
+        Ok(Self {
+            inner,
+            level: Compression::new(level),
+            pool,
+            buffer: Vec::with_capacity(threads * BLOCK_SIZE),
+            batch_size: threads * BLOCK_SIZE,
+            crc: Crc::new(),
+        })
+    }
+
+    /// Compress whatever is buffered as the final block, write the trailer and return the
+    /// underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.compress_buffer(true)?;
+        self.inner.write_all(&self.crc.sum().to_le_bytes())?;
+        // ISIZE is the input length modulo 2^32
+        self.inner.write_all(&self.crc.amount().to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    // Deflate the buffered blocks on the pool and write them in order
+    fn compress_buffer(&mut self, last: bool) -> io::Result<()> {
+        let blocks: Vec<&[u8]> = if self.buffer.is_empty() {
+            // The stream still needs a final block when the input ends on a batch boundary
+            vec![&[]]
+        } else {
+            self.buffer.chunks(BLOCK_SIZE).collect()
+        };
+        let final_index = last.then(|| blocks.len() - 1);
+        let level = self.level;
+        let compressed = self.pool.install(|| {
+            blocks
+                .par_iter()
+                .enumerate()
+                .map(|(i, block)| {
+                    let mut crc = Crc::new();
+                    crc.update(block);
+                    deflate_block(block, level, Some(i) == final_index).map(|out| (out, crc))
+                })
+                .collect::<io::Result<Vec<_>>>()
+        })?;
+        for (out, crc) in compressed {
+            self.inner.write_all(&out)?;
+            self.crc.combine(&crc);
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ParallelGzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buffer.len() == self.batch_size {
+            self.compress_buffer(false)?;
+        }
+        let n = buf.len().min(self.batch_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    // Only the underlying writer is flushed; flushing a partial block would make the output
+    // depend on how the input was written
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// A gzip header with no file name or modification time, flagging the level like zlib does
+fn header(level: u32) -> [u8; 10] {
+    let extra_flags = match level {
+        9 => 2,
+        0 | 1 => 4,
+        _ => 0,
+    };
+    // Magic, deflate, no flags, zero mtime, extra flags, unknown OS
+    [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, extra_flags, 0xff]
+}
+
+// Raw-deflate one block with a fresh compressor, ending it with a sync flush so the next
+// block's stream can follow it, or with the final block marker when `last`
+fn deflate_block(block: &[u8], level: Compression, last: bool) -> io::Result<Vec<u8>> {
+    let mut compress = Compress::new(level, false);
+    let flush = if last {
+        FlushCompress::Finish
+    } else {
+        FlushCompress::Sync
+    };
+    let mut out = Vec::with_capacity(block.len() / 2 + 1024);
+    loop {
+        if out.capacity() - out.len() < 1024 {
+            out.reserve(out.capacity());
+        }
+        let consumed = compress.total_in() as usize;
+        let status = compress
+            .compress_vec(&block[consumed..], &mut out, flush)
+            .map_err(io::Error::other)?;
+        let all_consumed = compress.total_in() as usize == block.len();
+        // A sync flush is complete once the compressor stops short of filling the output
+        match status {
+            Status::StreamEnd => return Ok(out),
+            _ if !last && all_consumed && out.len() < out.capacity() => return Ok(out),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Nothing-up-my-sleeve derivation of the Dory generators by hashing to the curve.
+//!
+//! Every generator is `hash_to_curve(DST, label || index)` using the simplified SWU map of
+//! RFC 9380 with `expand_message_xmd` over SHA3-256, so anyone can recompute any single
+//! generator from the domain-separation tag alone instead of replaying a seeded RNG stream.
+
+use crate::{
+    progress::{Phase, Progress},
+    raw_parameters::{generator_count, RawParameters, PROGRESS_CHUNK},
+};
+use ark_bls12_381::{g1, g2, G1Affine, G1Projective, G2Affine, G2Projective};
+use ark_ec::hashing::{
+    curve_maps::wb::WBMap, map_to_curve_hasher::MapToCurveBasedHasher, HashToCurve,
+};
+use ark_ff::field_hashers::DefaultFieldHasher;
+use rand::Rng;
+use rayon::prelude::*;
+use sha3::Sha3_256;
+use std::ops::Range;
+
+/// The domain-separation tag used when none is given.
+pub const DEFAULT_DST: &str = "SXT-DORY-PUBLIC-PARAMETERS-V1";
+
+// Suite identifiers appended to the user's tag, one per group
+const G1_SUITE: &str = "_BLS12381G1_XMD:SHA3-256_SSWU_RO_";
+const G2_SUITE: &str = "_BLS12381G2_XMD:SHA3-256_SSWU_RO_";
+
+// RFC 9380 limits the full tag to 255 bytes
+const MAX_DST_LEN: usize = 255 - G1_SUITE.len();
+
+type G1Hasher =
+    MapToCurveBasedHasher<G1Projective, DefaultFieldHasher<Sha3_256>, WBMap<g1::Config>>;
+type G2Hasher =
+    MapToCurveBasedHasher<G2Projective, DefaultFieldHasher<Sha3_256>, WBMap<g2::Config>>;
+
+/// Derives each Dory generator from a domain-separation tag and its position.
+pub struct GeneratorHasher {
+    dst: String,
+    g1: G1Hasher,
+    g2: G2Hasher,
+}
+
+impl GeneratorHasher {
+    pub fn new(dst: &str) -> Result<Self, String> {
+        if dst.is_empty() || dst.len() > MAX_DST_LEN {
+            return Err(format!(
+                "the domain-separation tag must be 1 to {} bytes, found {}",
+                MAX_DST_LEN,
+                dst.len()
+            ));
+        }
+        let hasher_error = |e| format!("failed to set up hash-to-curve: {}", e);
+        Ok(Self {
+            dst: dst.to_string(),
+            g1: G1Hasher::new(format!("{}{}", dst, G1_SUITE).as_bytes()).map_err(hasher_error)?,
+            g2: G2Hasher::new(format!("{}{}", dst, G2_SUITE).as_bytes()).map_err(hasher_error)?,
+        })
+    }
+
+    /// The domain-separation tag the generators are derived from.
+    pub fn dst(&self) -> &str {
+        &self.dst
+    }
+
+    /// `Γ_1[index]`.
+    pub fn gamma_1(&self, index: usize) -> G1Affine {
+        self.hash_g1("Gamma_1", index)
+    }
+
+    /// `Γ_2[index]`.
+    pub fn gamma_2(&self, index: usize) -> G2Affine {
+        self.hash_g2("Gamma_2", index)
+    }
+
+    pub fn h_1(&self) -> G1Affine {
+        self.hash_g1("H_1", 0)
+    }
+
+    pub fn h_2(&self) -> G2Affine {
+        self.hash_g2("H_2", 0)
+    }
+
+    /// `Γ_2,fin`.
+    pub fn gamma_2_fin(&self) -> G2Affine {
+        self.hash_g2("Gamma_2_fin", 0)
+    }
+
+    /// Derive all of the generators for `nu`, reporting each one to `progress`.
+    pub fn derive(&self, nu: usize, progress: &dyn Progress) -> RawParameters {
+        progress.start(Phase::Generators, Some(generator_count(nu)));
+        let (h_1, h_2, gamma_2_fin) = (self.h_1(), self.h_2(), self.gamma_2_fin());
+        progress.advance(3);
+        let (gamma_1, gamma_2) = self.derive_range(0..1 << nu, progress);
+        progress.finish();
+        RawParameters {
+            gamma_1,
+            gamma_2,
+            h_1,
+            h_2,
+            gamma_2_fin,
+        }
+    }
+
+    /// Derive `Γ_1` and `Γ_2` at the given indices, advancing `progress` for each generator.
+    pub fn derive_range(
+        &self,
+        indices: Range<usize>,
+        progress: &dyn Progress,
+    ) -> (Vec<G1Affine>, Vec<G2Affine>) {
+        let gamma_1 = derive_chunked(indices.clone(), |i| self.gamma_1(i), progress);
+        let gamma_2 = derive_chunked(indices, |i| self.gamma_2(i), progress);
+        (gamma_1, gamma_2)
+    }
+
+    /// Recompute the fixed generators and `samples` randomly chosen indices of `Γ_1` and `Γ_2`,
+    /// and check they match `parameters`.
+    pub fn spot_check(
+        &self,
+        parameters: &RawParameters,
+        samples: usize,
+        rng: &mut impl Rng,
+    ) -> Result<(), String> {
+        let mismatch = |name: &str| Err(format!("{} was not derived from {:?}", name, self.dst));
+        if parameters.h_1 != self.h_1() {
+            return mismatch("H_1");
+        }
+        if parameters.h_2 != self.h_2() {
+            return mismatch("H_2");
+        }
+        if parameters.gamma_2_fin != self.gamma_2_fin() {
+            return mismatch("Γ_2,fin");
+        }
+        let len = parameters.gamma_1.len();
+        for _ in 0..samples {
+            let index = rng.gen_range(0..len);
+            if parameters.gamma_1[index] != self.gamma_1(index) {
+                return mismatch(&format!("Γ_1[{}]", index));
+            }
+            if parameters.gamma_2[index] != self.gamma_2(index) {
+                return mismatch(&format!("Γ_2[{}]", index));
+            }
+        }
+        Ok(())
+    }
+
+    fn hash_g1(&self, label: &str, index: usize) -> G1Affine {
+        self.g1
+            .hash(&message(label, index))
+            .expect("the SWU map is defined for every field element")
+    }
+
+    fn hash_g2(&self, label: &str, index: usize) -> G2Affine {
+        self.g2
+            .hash(&message(label, index))
+            .expect("the SWU map is defined for every field element")
+    }
+}
+
+// The label followed by the big-endian 64-bit index
+fn message(label: &str, index: usize) -> Vec<u8> {
+    let mut message = label.as_bytes().to_vec();
+    message.extend_from_slice(&(index as u64).to_be_bytes());
+    message
+}
+
+// Derive the generators at `indices` in parallel, advancing `progress` once per chunk of at most
+// `PROGRESS_CHUNK` generators rather than once per generator, since every report takes a lock
+fn derive_chunked<T: Send>(
+    indices: Range<usize>,
+    derive: impl Fn(usize) -> T + Sync + Send,
+    progress: &dyn Progress,
+) -> Vec<T> {
+    indices
+        .into_par_iter()
+        .with_max_len(PROGRESS_CHUNK)
+        .fold(Vec::new, |mut derived, i| {
+            derived.push(derive(i));
+            derived
+        })
+        .map(|derived| {
+            progress.advance(derived.len() as u64);
+            derived
+        })
+        .flatten()
+        .collect()
+}
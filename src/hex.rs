@@ -0,0 +1,19 @@
+/// Lowercase hex encoding of arbitrary bytes.
+pub fn encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode a hex string, with or without a `0x` prefix.
+pub fn decode(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.len().is_multiple_of(2) {
+        return Err(format!("hex string has odd length {}", s.len()));
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair).map_err(|_| "hex string is not ASCII")?;
+            u8::from_str_radix(pair, 16).map_err(|_| format!("invalid hex digits {:?}", pair))
+        })
+        .collect()
+}
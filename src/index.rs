@@ -0,0 +1,48 @@
+use crate::manifest::ManifestEntry;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// File name of the index written next to the archives of a multi-`nu` run.
+pub const INDEX_FILE: &str = "dory-params-index.json";
+
+/// The archives produced for one `nu`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub nu: usize,
+    /// The prover archive first, then the verifier archive if one was written
+    pub archives: Vec<ManifestEntry>,
+}
+
+/// Lists every bundle written by a run that covered several `nu` values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleIndex {
+    /// Version of this generator
+    pub generator_version: String,
+    pub bundles: Vec<IndexEntry>,
+}
+
+impl Default for BundleIndex {
+    fn default() -> Self {
+        Self {
+            generator_version: env!("CARGO_PKG_VERSION").to_string(),
+            bundles: Vec::new(),
+        }
+    }
+}
+
+impl BundleIndex {
+    /// Record the digests of the archives written for `nu`.
+    pub fn add(&mut self, nu: usize, archives: Vec<ManifestEntry>) {
+        self.bundles.push(IndexEntry { nu, archives });
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load_from_file(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
@@ -0,0 +1,243 @@
+//! Describe a bundle archive without loading its parameters.
+//!
+//! The archive is streamed once: every member is hashed on the way past, the public
+//! parameters are described from their header, the blitzar handle from its size, and the small
+//! JSON members are parsed. Anything inconsistent is reported as a warning rather than an
+//! error, since the point is to look at archives of unknown origin.
+
+use crate::{
+    archive::{open_archive, ArchiveFormat, BLITZAR_HANDLE_FILE, PUBLIC_PARAMETERS_FILE},
+    hex,
+    manifest::{Manifest, ManifestEntry, MANIFEST_FILE, SIGNATURE_FILE},
+    metadata::{BundleMetadata, METADATA_FILE},
+    sizes::{blitzar_partitions, parse_nu_header, ArtifactSizes, BLITZAR_PARTITION_WIDTH},
+};
+use ring::digest::{Context, SHA256};
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+};
+
+// Bytes kept from a JSON member for parsing; real metadata and manifests are a few kB
+const MAX_JSON_SIZE: usize = 1 << 20;
+
+/// Everything [`inspect_archive`] learned about an archive.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Inspection {
+    /// Format detected from the archive's first bytes
+    pub format: String,
+    /// Size of the archive file
+    pub size: u64,
+    /// Every regular member, in archive order
+    pub members: Vec<ManifestEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_parameters: Option<PublicParametersSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blitzar_handle: Option<BlitzarHandleSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BundleMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<Manifest>,
+    /// Whether the manifest is signed; `verify --public-key` checks the signature
+    pub signed: bool,
+    /// Disagreements between the members, their headers, the metadata and the manifest
+    pub warnings: Vec<String>,
+}
+
+/// The header of `public_parameters.bin`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicParametersSummary {
+    /// The `max_nu` the member starts with
+    pub nu: usize,
+    /// Length of each of `Γ_1` and `Γ_2`, `2^nu`
+    pub vector_length: u64,
+}
+
+/// The layout of `blitzar_handle.bin`, which has no header and is described by its size.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlitzarHandleSummary {
+    /// The precomputation window: generators per partition whose subset sums are tabulated
+    pub partition_width: u64,
+    /// Precomputed G1 points per partition, `2^partition_width`
+    pub points_per_partition: u64,
+    pub partitions: u64,
+    /// Generators the handle covers, counting the padding of the last partition
+    pub generators: u64,
+}
+
+/// Stream through `archive_path` and describe its members.
+pub fn inspect_archive(archive_path: &Path) -> io::Result<Inspection> {
+    let format = ArchiveFormat::detect(archive_path)?;
+    let size = fs::metadata(archive_path)?.len();
+    let mut inspection = Inspection {
+        format: format.to_string(),
+        size,
+        members: Vec::new(),
+        public_parameters: None,
+        blitzar_handle: None,
+        metadata: None,
+        manifest: None,
+        signed: false,
+        warnings: Vec::new(),
+    };
+
+    let mut archive = open_archive(archive_path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let keep = match name.as_str() {
+            PUBLIC_PARAMETERS_FILE => 8,
+            METADATA_FILE | MANIFEST_FILE => MAX_JSON_SIZE,
+            _ => 0,
+        };
+        let (size, sha256, head) = hash_member(&mut entry, keep)?;
+        match name.as_str() {
+            PUBLIC_PARAMETERS_FILE => {
+                inspection.public_parameters = describe_public_parameters(&head, size)
+                    .map_err(|warning| inspection.warnings.push(warning))
+                    .ok();
+            }
+            BLITZAR_HANDLE_FILE => {
+                inspection.blitzar_handle = describe_blitzar_handle(size)
+                    .map_err(|warning| inspection.warnings.push(warning))
+                    .ok();
+            }
+            METADATA_FILE => {
+                inspection.metadata = serde_json::from_slice(&head)
+                    .map_err(|e| {
+                        inspection
+                            .warnings
+                            .push(format!("unreadable {}: {}", name, e))
+                    })
+                    .ok();
+            }
+            MANIFEST_FILE => {
+                inspection.manifest = serde_json::from_slice(&head)
+                    .map_err(|e| {
+                        inspection
+                            .warnings
+                            .push(format!("unreadable {}: {}", name, e))
+                    })
+                    .ok();
+            }
+            SIGNATURE_FILE => inspection.signed = true,
+            _ => {}
+        }
+        inspection
+            .members
+            .push(ManifestEntry { name, size, sha256 });
+    }
+
+    let warnings = cross_check(&inspection);
+    inspection.warnings.extend(warnings);
+    Ok(inspection)
+}
+
+// Hash a member to its end, returning its size, hex digest and up to `keep` leading bytes
+fn hash_member(member: &mut impl Read, keep: usize) -> io::Result<(u64, String, Vec<u8>)> {
+    let mut context = Context::new(&SHA256);
+    let mut head = Vec::new();
+    let mut buf = vec![0u8; 1 << 20];
+    let mut size = 0u64;
+    loop {
+        let n = match member.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        context.update(&buf[..n]);
+        let kept = (keep - head.len()).min(n);
+        head.extend_from_slice(&buf[..kept]);
+        size += n as u64;
+    }
+    Ok((size, hex::encode(context.finish().as_ref()), head))
+}
+
+// Read `nu` from the start of the public parameters and check the member's size against it
+fn describe_public_parameters(head: &[u8], size: u64) -> Result<PublicParametersSummary, String> {
+    let nu = parse_nu_header(head).map_err(|e| format!("{}: {}", PUBLIC_PARAMETERS_FILE, e))?;
+    let expected = ArtifactSizes::for_nu(nu).public_parameters;
+    if size != expected {
+        return Err(format!(
+            "{} records nu = {} but is {} bytes, expected {}",
+            PUBLIC_PARAMETERS_FILE, nu, size, expected
+        ));
+    }
+    Ok(PublicParametersSummary {
+        nu,
+        vector_length: 1 << nu,
+    })
+}
+
+// Derive the handle's layout from its size
+fn describe_blitzar_handle(size: u64) -> Result<BlitzarHandleSummary, String> {
+    let partitions = blitzar_partitions(size).ok_or_else(|| {
+        format!(
+            "{} is {} bytes, not a whole number of partitions",
+            BLITZAR_HANDLE_FILE, size
+        )
+    })?;
+    Ok(BlitzarHandleSummary {
+        partition_width: BLITZAR_PARTITION_WIDTH,
+        points_per_partition: 1 << BLITZAR_PARTITION_WIDTH,
+        partitions,
+        generators: partitions * BLITZAR_PARTITION_WIDTH,
+    })
+}
+
+// Compare what the members say about the bundle with each other and with the manifest
+fn cross_check(inspection: &Inspection) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let nu = inspection.public_parameters.map(|summary| summary.nu);
+    if let (Some(nu), Some(handle)) = (nu, inspection.blitzar_handle) {
+        let needed = (1u64 << nu).div_ceil(BLITZAR_PARTITION_WIDTH);
+        if handle.partitions != needed {
+            warnings.push(format!(
+                "{} has {} partitions, but nu = {} needs {}",
+                BLITZAR_HANDLE_FILE, handle.partitions, nu, needed
+            ));
+        }
+    }
+    if let (Some(nu), Some(metadata)) = (nu, &inspection.metadata) {
+        if metadata.nu != nu {
+            warnings.push(format!(
+                "{} records nu = {}, but the parameters are for nu = {}",
+                METADATA_FILE, metadata.nu, nu
+            ));
+        }
+    }
+
+    let Some(manifest) = &inspection.manifest else {
+        return warnings;
+    };
+    if let Some(nu) = nu.filter(|&nu| nu != manifest.nu) {
+        warnings.push(format!(
+            "{} records nu = {}, but the parameters are for nu = {}",
+            MANIFEST_FILE, manifest.nu, nu
+        ));
+    }
+    for listed in &manifest.members {
+        match inspection
+            .members
+            .iter()
+            .find(|member| member.name == listed.name)
+        {
+            None => warnings.push(format!(
+                "{} lists {}, which the archive does not hold",
+                MANIFEST_FILE, listed.name
+            )),
+            Some(member) if member != listed => warnings.push(format!(
+                "{} is {} bytes with SHA-256 {}, {} records {} bytes with SHA-256 {}",
+                member.name, member.size, member.sha256, MANIFEST_FILE, listed.size, listed.sha256
+            )),
+            Some(_) => {}
+        }
+    }
+    warnings
+}
@@ -0,0 +1,130 @@
+//! Cleanup of partial outputs when the process is interrupted by SIGINT or SIGTERM.
+//!
+//! The signals are blocked in every thread and received by a dedicated thread with `sigwait`,
+//! so the cleanup runs as ordinary code rather than inside a signal handler. Paths being
+//! written are registered while they are incomplete; on a signal they are removed and the
+//! process exits with 128 plus the signal number, like a shell reports a killed command.
+
+use std::{
+    fs, io,
+    mem::MaybeUninit,
+    path::{Path, PathBuf},
+    process, ptr,
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+// Files and directories that are incomplete until they are unregistered
+static PARTIAL_OUTPUTS: PartialOutputs = PartialOutputs::new();
+
+// Paths to remove if the process is interrupted
+pub(crate) struct PartialOutputs(Mutex<Vec<PathBuf>>);
+
+impl PartialOutputs {
+    pub(crate) const fn new() -> Self {
+        Self(Mutex::new(Vec::new()))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<PathBuf>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn register(&self, path: &Path) {
+        self.lock().push(path.to_path_buf());
+    }
+
+    pub(crate) fn unregister(&self, path: &Path) {
+        let mut partial_outputs = self.lock();
+        if let Some(i) = partial_outputs.iter().rposition(|partial| partial == path) {
+            partial_outputs.remove(i);
+        }
+    }
+
+    // Remove every registered path, returning the ones removed and the list, whose lock keeps
+    // new partial outputs from being registered for as long as it is held
+    pub(crate) fn remove_all(&self) -> (MutexGuard<'_, Vec<PathBuf>>, Vec<PathBuf>) {
+        let partial_outputs = self.lock();
+        let removed = partial_outputs
+            .iter()
+            .filter(|path| remove(path).is_ok())
+            .cloned()
+            .collect();
+        (partial_outputs, removed)
+    }
+}
+
+/// Block SIGINT and SIGTERM and start a thread that waits for them.
+///
+/// On a signal every registered path is removed, then `on_interrupt` runs with the signal
+/// number and the paths removed, and the process exits with [`exit_code`]. Call this before
+/// starting any other thread, since only threads started afterwards inherit the blocked signals.
+pub fn install(on_interrupt: impl FnOnce(i32, &[PathBuf]) + Send + 'static) -> io::Result<()> {
+    let mut signals = MaybeUninit::<libc::sigset_t>::uninit();
+    // SAFETY: `sigemptyset` initializes the set before it is added to or read
+    let signals = unsafe {
+        libc::sigemptyset(signals.as_mut_ptr());
+        libc::sigaddset(signals.as_mut_ptr(), libc::SIGINT);
+        libc::sigaddset(signals.as_mut_ptr(), libc::SIGTERM);
+        signals.assume_init()
+    };
+    let mut old_mask = MaybeUninit::<libc::sigset_t>::uninit();
+    // SAFETY: `signals` is a valid set and `old_mask` is written before it is read
+    let error = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &signals, old_mask.as_mut_ptr()) };
+    if error != 0 {
+        return Err(io::Error::from_raw_os_error(error));
+    }
+
+    let spawned = thread::Builder::new()
+        .name("signals".to_string())
+        .spawn(move || {
+            let mut signal = 0;
+            // SAFETY: `signals` is a valid set and `signal` outlives the call
+            if unsafe { libc::sigwait(&signals, &mut signal) } != 0 {
+                return;
+            }
+            // Holding the lock until exit keeps new partial outputs from being registered
+            let (_partial_outputs, removed) = PARTIAL_OUTPUTS.remove_all();
+            on_interrupt(signal, &removed);
+            process::exit(exit_code(signal));
+        });
+    if let Err(e) = spawned {
+        // Without the thread nothing would receive the signals, so they are unblocked again
+        // SAFETY: `pthread_sigmask` succeeded above, so `old_mask` holds the previous mask
+        unsafe { libc::pthread_sigmask(libc::SIG_SETMASK, old_mask.as_ptr(), ptr::null_mut()) };
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Remove `path`, a file or directory, if the process is interrupted before it is unregistered.
+pub fn register(path: &Path) {
+    PARTIAL_OUTPUTS.register(path);
+}
+
+/// `path` is complete, or already removed, and must be left alone on interrupt.
+pub fn unregister(path: &Path) {
+    PARTIAL_OUTPUTS.unregister(path);
+}
+
+/// The process exit code after being interrupted by `signal`.
+pub fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
+/// The conventional name of SIGINT or SIGTERM.
+pub fn signal_name(signal: i32) -> &'static str {
+    match signal {
+        libc::SIGINT => "SIGINT",
+        libc::SIGTERM => "SIGTERM",
+        _ => "signal",
+    }
+}
+
+// Remove a file, or a directory and its contents
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,42 @@
+//! Generation, packaging and loading of the Dory public parameters used by the SxT network.
+
+pub mod archive;
+mod atomic;
+pub mod bench;
+mod bundle;
+pub mod ceremony;
+pub mod checkpoint;
+pub mod diff;
+pub mod error;
+pub mod extend;
+pub mod gzip;
+pub mod hash_to_curve;
+mod hex;
+pub mod index;
+pub mod inspect;
+pub mod interrupt;
+pub mod manifest;
+pub mod metadata;
+mod packaging;
+pub mod preflight;
+pub mod progress;
+pub mod raw_parameters;
+pub mod seed;
+pub mod sizes;
+mod temp_dir;
+// The file keeps its own `mod tests` wrapper from when it was included by main.rs
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+pub mod verify;
+
+pub use bundle::{
+    check_archive_names, generate, generate_hash_to_curve, load_bundle, load_bundle_parts,
+    load_public_parameters, prover_setup, write_bundle, BlitzarHandle, BundleOptions, BundleOutput,
+    LoadedBundle, TierHandles,
+};
+pub use error::ParamGenError;
+pub use packaging::{
+    package, package_tiers, resized_archive_name, tier_archive_name, TieredOutput,
+};
+pub use seed::Seed;
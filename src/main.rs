@@ -1,35 +1,464 @@
-mod tests;
-
-use clap::Parser;
-use flate2::{write::GzEncoder, Compression};
-use indicatif::{ProgressBar, ProgressStyle};
-use proof_of_sql::proof_primitive::dory::{ProverSetup, PublicParameters};
-use rand::SeedableRng;
-use rand_chacha::ChaCha20Rng;
+use clap::{Parser, Subcommand, ValueEnum};
+use generate_sxt_dory_params::{
+    archive::{self, ArchiveFormat, CompressionOptions},
+    bench::{self, CodecBenchmark},
+    ceremony, check_archive_names,
+    checkpoint::{WorkDir, CHECKPOINT_FILE},
+    diff::{self, BundleDiff, Relation},
+    extend, generate, generate_hash_to_curve, hash_to_curve,
+    inspect::{self, Inspection},
+    interrupt, load_public_parameters,
+    manifest::{self, ManifestEntry},
+    metadata::{BundleMetadata, METADATA_FILE},
+    package, package_tiers,
+    preflight::{Requirements, ResourceCheck},
+    progress::{JsonProgress, Progress, TerminalProgress},
+    raw_parameters::RawParameters,
+    resized_archive_name,
+    seed::SeedSource,
+    sizes::{format_mb, tar_size, ArtifactSizes, BLITZAR_PARTITION_WIDTH, MAX_NU},
+    tier_archive_name, verify, BundleOptions, BundleOutput, ParamGenError, Seed,
+};
+use ring::signature::Ed25519KeyPair;
+use serde_json::{json, Value};
 use std::{
-    fs::{self, File},
-    path::Path,
-    thread,
-    time::{Duration, Instant},
+    cell::RefCell,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Instant,
 };
-use tar::Builder;
 
 // Command-line argument parser structure
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// The value for `nu` (number of public parameters)
-    #[arg(short, long, default_value_t = 15)]
-    nu: usize,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The value for `nu` (number of public parameters), or a comma-separated list of values
+    /// to write one archive per `nu`, cutting the smaller tiers from the largest one
+    #[arg(short, long, value_delimiter = ',', default_value = "15", value_parser = parse_nu)]
+    nu: Vec<usize>,
+
+    /// An inclusive range of `nu` values such as `8..=20`, writing one archive per `nu`
+    #[arg(long, value_name = "RANGE", value_parser = parse_nu_range, conflicts_with = "nu")]
+    nu_range: Option<RangeInclusive<usize>>,
+
+    #[command(flatten)]
+    seed: SeedArgs,
+
+    /// Derive every generator by hashing to the curve under this domain-separation tag
+    /// instead of sampling them from the seeded RNG
+    #[arg(
+        long,
+        value_name = "DST",
+        num_args = 0..=1,
+        default_missing_value = hash_to_curve::DEFAULT_DST,
+        conflicts_with = "seed_input"
+    )]
+    hash_to_curve: Option<String>,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// Start even if the disk space or memory checks fail
+    #[arg(long)]
+    force: bool,
+
+    /// Record a checkpoint in this directory after each phase, so an interrupted run can be
+    /// continued with --resume
+    #[arg(long, value_name = "DIR", conflicts_with = "resume")]
+    work_dir: Option<PathBuf>,
+
+    /// Continue an interrupted run from the checkpoints in WORKDIR, given the same options,
+    /// skipping every finished phase whose artifacts still match their recorded digests
+    #[arg(long, value_name = "WORKDIR")]
+    resume: Option<PathBuf>,
+
+    /// Print human-readable output, or newline-delimited JSON events for orchestrators; with a
+    /// subcommand, pass it after the subcommand's name
+    #[arg(long, value_enum, global = true, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// The banner, progress bars and status lines
+    Text,
+    /// One JSON event per line on stdout, with no banner or progress bars
+    Json,
+}
+
+// Options selecting the RNG seed
+#[derive(clap::Args, Debug)]
+struct SeedArgs {
+    /// UTF-8 string to seed the RNG with, zero-padded to 32 bytes
+    #[arg(long, group = "seed_input")]
+    seed: Option<String>,
+
+    /// 64-character hex string to seed the RNG with
+    #[arg(long, group = "seed_input")]
+    seed_hex: Option<String>,
+
+    /// Path to a file containing exactly 32 raw seed bytes
+    #[arg(long, group = "seed_input")]
+    seed_file: Option<PathBuf>,
+}
+
+// Options controlling which archives are written and where
+#[derive(clap::Args, Debug)]
+struct OutputArgs {
+    /// Also derive the verifier setup and package it as dory-verifier-params.<format>
+    #[arg(long)]
+    verifier_setup: bool,
+
+    /// Ed25519 key (PKCS#8 DER or raw 32-byte seed) used to sign the archive manifest
+    #[arg(long)]
+    signing_key: Option<PathBuf>,
+
+    /// Directory to write the archives into
+    #[arg(long, default_value = ".")]
+    out_dir: PathBuf,
+
+    /// File name of the prover archive [default: dory-params.<format>]
+    #[arg(long)]
+    archive_name: Option<String>,
+
+    /// File name of the verifier archive written with --verifier-setup
+    /// [default: dory-verifier-params.<format>]
+    #[arg(long)]
+    verifier_archive_name: Option<String>,
+
+    /// Also keep each archive's members, uncompressed, in a directory next to it named after
+    /// the archive
+    #[arg(long)]
+    keep_intermediates: bool,
+
+    /// Archive format: tar, tar.gz, tar.zst or tar.xz
+    #[arg(long, default_value = "tar.gz")]
+    format: ArchiveFormat,
+
+    /// Compression level, up to 9 for gzip and xz and 22 for zstd [default: 6 for gzip and xz,
+    /// 3 for zstd]
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=22))]
+    compression_level: Option<u32>,
+
+    /// Threads compressing the archive in parallel [default: all cores]
+    #[arg(long, value_name = "THREADS", value_parser = clap::value_parser!(u64).range(1..))]
+    compression_threads: Option<u64>,
 }
 
-// Function to calculate the estimated file size based on nu
-fn estimated_file_size(nu: usize) -> f64 {
-    let base_size = 6.3; // 6.3 MB for nu = 4
-    if nu >= 4 {
-        base_size * 2_f64.powi((nu as i32) - 4)
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check that an existing archive unpacks and rebuilds a prover setup
+    Verify {
+        /// Path to the archive to check, in any supported format
+        archive: PathBuf,
+
+        /// Also regenerate from the recorded seed and nu and compare byte-for-byte
+        #[arg(long)]
+        regenerate: bool,
+
+        /// Ed25519 public key (raw or hex) the manifest signature must verify against
+        #[arg(long)]
+        public_key: Option<PathBuf>,
+
+        /// Recompute this many random generators of a hash-to-curve archive and compare them
+        #[arg(long, value_name = "SAMPLES")]
+        spot_check: Option<usize>,
+    },
+
+    /// Describe an archive's members, parameters, blitzar handle and manifest without loading it
+    Inspect {
+        /// Path to the archive to describe, in any supported format
+        archive: PathBuf,
+    },
+
+    /// Compare two prover archives element by element and tell whether they are equivalent,
+    /// one is a prefix of the other, or they are unrelated, exiting with code 15 unless they are
+    /// equivalent
+    Diff {
+        /// The first prover archive
+        first: PathBuf,
+
+        /// The second prover archive
+        second: PathBuf,
+    },
+
+    /// Extend an existing prover archive to a larger `nu`, generating only the missing generators
+    Extend {
+        /// The prover archive to extend
+        #[arg(long)]
+        from: PathBuf,
+
+        /// The `nu` to extend to
+        #[arg(long, value_parser = parse_nu)]
+        to: usize,
+
+        #[command(flatten)]
+        output: OutputArgs,
+
+        /// Start even if the disk space or memory checks fail
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Derive a bundle for a smaller `nu` from the prefix of an existing prover archive
+    Truncate {
+        /// The prover archive to truncate
+        #[arg(long)]
+        from: PathBuf,
+
+        /// The `nu` to truncate to
+        #[arg(long, value_parser = parse_nu)]
+        to: usize,
+
+        #[command(flatten)]
+        output: OutputArgs,
+
+        /// Start even if the disk space or memory checks fail
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Recompress an existing archive in every format and report ratio and throughput
+    BenchCompression {
+        /// The archive whose members are recompressed
+        archive: PathBuf,
+
+        /// Threads compressing in parallel [default: all cores]
+        #[arg(long, value_name = "THREADS", value_parser = clap::value_parser!(u64).range(1..))]
+        compression_threads: Option<u64>,
+    },
+
+    /// Run a multi-party ceremony that rerandomizes the generators
+    Ceremony {
+        #[command(subcommand)]
+        action: CeremonyCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CeremonyCommand {
+    /// Start a ceremony directory from seed-derived parameters
+    Init {
+        /// Directory to hold the transcript and every state
+        dir: PathBuf,
+
+        /// The value for `nu` (number of public parameters)
+        #[arg(short, long, default_value_t = 15, value_parser = parse_nu)]
+        nu: usize,
+
+        #[command(flatten)]
+        seed: SeedArgs,
+    },
+
+    /// Rerandomize the latest state with fresh entropy and append a proof
+    Contribute {
+        /// The ceremony directory received from the previous participant
+        dir: PathBuf,
+
+        /// Name recorded in the transcript for this contribution
+        #[arg(long)]
+        name: String,
+    },
+
+    /// Check every state digest and contribution proof in a ceremony directory
+    Verify {
+        /// The ceremony directory to check
+        dir: PathBuf,
+    },
+
+    /// Verify the ceremony and package its final state like a normal generation run
+    Finalize {
+        /// The ceremony directory to finalize
+        dir: PathBuf,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+impl Args {
+    // The name reported in the JSON `start` and `summary` events
+    fn command_name(&self) -> &'static str {
+        match &self.command {
+            None => "generate",
+            Some(Command::Verify { .. }) => "verify",
+            Some(Command::Inspect { .. }) => "inspect",
+            Some(Command::Diff { .. }) => "diff",
+            Some(Command::Extend { .. }) => "extend",
+            Some(Command::Truncate { .. }) => "truncate",
+            Some(Command::BenchCompression { .. }) => "bench-compression",
+            Some(Command::Ceremony { action }) => match action {
+                CeremonyCommand::Init { .. } => "ceremony init",
+                CeremonyCommand::Contribute { .. } => "ceremony contribute",
+                CeremonyCommand::Verify { .. } => "ceremony verify",
+                CeremonyCommand::Finalize { .. } => "ceremony finalize",
+            },
+        }
+    }
+
+    // The requested nu values in increasing order, without duplicates
+    fn nus(&self) -> Vec<usize> {
+        let mut nus = match &self.nu_range {
+            Some(range) => range.clone().collect(),
+            None => self.nu.clone(),
+        };
+        nus.sort_unstable();
+        nus.dedup();
+        nus
+    }
+
+    // The prover archive name for `nu`, as `package_tiers` will name it
+    fn archive_name(&self, nu: usize) -> String {
+        tier_archive_name(&self.output.archive_name(), nu, self.nus().len())
+    }
+}
+
+// Parse a nu value, capped at the largest nu any parameter set can have
+fn parse_nu(s: &str) -> Result<usize, String> {
+    s.trim()
+        .parse::<usize>()
+        .map_err(|e| format!("invalid nu {:?}: {}", s, e))
+        .and_then(check_nu)
+}
+
+fn check_nu(nu: usize) -> Result<usize, String> {
+    if nu > MAX_NU {
+        return Err(format!("nu = {} is above the maximum of {}", nu, MAX_NU));
+    }
+    Ok(nu)
+}
+
+// Parse `a..=b` or `a..b` into an inclusive range of nu values
+fn parse_nu_range(s: &str) -> Result<RangeInclusive<usize>, String> {
+    let parse = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|e| format!("invalid nu {:?}: {}", n, e))
+    };
+    let range = if let Some((start, end)) = s.split_once("..=") {
+        parse(start)?..=parse(end)?
+    } else if let Some((start, end)) = s.split_once("..") {
+        let end = parse(end)?;
+        parse(start)?..=end.checked_sub(1).ok_or("the range is empty")?
     } else {
-        base_size
+        return Err(format!("expected a range like 8..=20, found {:?}", s));
+    };
+    if range.is_empty() {
+        return Err(format!("the range {:?} is empty", s));
+    }
+    check_nu(*range.end())?;
+    Ok(range)
+}
+
+impl SeedArgs {
+    // Resolve the seed from whichever seed option was given, falling back to the default
+    fn resolve(&self) -> Result<Seed, String> {
+        if let Some(s) = &self.seed {
+            Seed::from_string(s)
+        } else if let Some(hex) = &self.seed_hex {
+            Seed::from_hex(hex)
+        } else if let Some(path) = &self.seed_file {
+            Seed::from_file(path.clone())
+        } else {
+            Ok(Seed::default())
+        }
+    }
+}
+
+impl OutputArgs {
+    // Load the signing key, so a bad key fails before any long-running work starts
+    fn signing_key(&self) -> Result<Option<Ed25519KeyPair>, ParamGenError> {
+        self.signing_key
+            .as_deref()
+            .map(manifest::load_signing_key)
+            .transpose()
+            .map_err(ParamGenError::SigningKey)
+    }
+
+    // Archive names default to the chosen format's extension
+    fn archive_name(&self) -> String {
+        self.archive_name
+            .clone()
+            .unwrap_or_else(|| self.format.default_archive_name())
+    }
+
+    fn verifier_archive_name(&self) -> String {
+        self.verifier_archive_name
+            .clone()
+            .unwrap_or_else(|| self.format.default_verifier_archive_name())
+    }
+
+    // Names for a bundle derived for `nu`, where only the default names are suffixed by `nu`
+    fn resized_archive_names(&self, nu: usize) -> (String, String) {
+        (
+            resized_archive_name(
+                self.archive_name.as_deref(),
+                &self.format.default_archive_name(),
+                nu,
+            ),
+            resized_archive_name(
+                self.verifier_archive_name.as_deref(),
+                &self.format.default_verifier_archive_name(),
+                nu,
+            ),
+        )
+    }
+
+    fn compression(&self) -> CompressionOptions {
+        CompressionOptions {
+            level: self.compression_level,
+            ..compression_options(self.format, self.compression_threads)
+        }
+    }
+
+    // Reject a level the format does not support or an invalid SOURCE_DATE_EPOCH before any
+    // work, with the error the archive writer would report
+    fn check_archive_settings(&self) -> Result<(), ParamGenError> {
+        self.compression()
+            .check_level()
+            .and_then(|()| archive::source_date_epoch().map(drop))
+            .map_err(|source| ParamGenError::Archive {
+                path: self.out_dir.join(self.archive_name()),
+                source,
+            })
+    }
+
+    // Reject archive names that would escape the output directory or collide, before any work
+    fn check_archive_names(&self) -> Result<(), ParamGenError> {
+        check_archive_names(
+            &self.archive_name(),
+            &self.verifier_archive_name(),
+            self.verifier_setup,
+        )
+    }
+
+    fn into_bundle_options(self, seed: Seed, signing_key: Option<Ed25519KeyPair>) -> BundleOptions {
+        BundleOptions {
+            seed,
+            archive_name: self.archive_name(),
+            verifier_archive_name: self.verifier_archive_name(),
+            compression: self.compression(),
+            out_dir: self.out_dir,
+            verifier_setup: self.verifier_setup,
+            hash_to_curve_dst: None,
+            parent_archive: None,
+            keep_intermediates: self.keep_intermediates,
+            signing_key,
+        }
+    }
+}
+
+// The format's default level on the requested number of threads, or on every core
+fn compression_options(format: ArchiveFormat, threads: Option<u64>) -> CompressionOptions {
+    let options = CompressionOptions::new(format);
+    CompressionOptions {
+        threads: threads.map_or(options.threads, |threads| threads as usize),
+        ..options
     }
 }
 
@@ -46,115 +475,772 @@ fn print_banner() {
     println!("{}", banner);
 }
 
-fn main() {
-    print_banner();
+// Sends status lines and progress to the terminal or, with `--output-format json`, as events
+struct Reporter {
+    terminal: TerminalProgress,
+    json: Option<JsonProgress>,
+    // Every artifact reported so far, repeated in the final summary
+    artifacts: RefCell<Vec<Value>>,
+}
+
+impl Reporter {
+    fn new(format: OutputFormat) -> Self {
+        Self {
+            terminal: TerminalProgress::default(),
+            json: (format == OutputFormat::Json).then(JsonProgress::default),
+            artifacts: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn progress(&self) -> &dyn Progress {
+        match &self.json {
+            Some(json) => json,
+            None => &self.terminal,
+        }
+    }
+
+    // A status line, printed as is or sent as a `message` event
+    fn say(&self, text: &str) {
+        self.progress().message(text);
+    }
+
+    // An event with no text counterpart, dropped in text mode
+    fn event(&self, event: Value) {
+        if let Some(json) = &self.json {
+            json.emit(event);
+        }
+    }
+
+    // Report a written file along with its digest
+    fn artifact(&self, path: &Path, entry: &ManifestEntry) {
+        self.say(&format!("Wrote {}", path.display()));
+        if self.json.is_some() {
+            let artifact = json!({
+                "path": path,
+                "size": entry.size,
+                "sha256": entry.sha256,
+            });
+            self.artifacts.borrow_mut().push(artifact.clone());
+            self.event(json!({ "event": "artifact", "artifact": artifact }));
+        }
+    }
+
+    // Print or emit the result of compressing with one format
+    fn benchmark(&self, benchmark: &CodecBenchmark) {
+        if self.json.is_none() {
+            println!(
+                "  {:<8} level {:>2}  {:>12}  ratio {:.4}  compress {:>9}/s  decompress {:>9}/s",
+                benchmark.format,
+                benchmark.level,
+                format_mb(benchmark.compressed_size),
+                benchmark.ratio(),
+                format_mb(benchmark.compress_throughput() as u64),
+                format_mb(benchmark.decompress_throughput() as u64),
+            );
+            return;
+        }
+        self.event(json!({
+            "event": "benchmark",
+            "format": benchmark.format.to_string(),
+            "level": benchmark.level,
+            "uncompressed_size": benchmark.uncompressed_size,
+            "compressed_size": benchmark.compressed_size,
+            "ratio": benchmark.ratio(),
+            "compress_ms": benchmark.compress_time.as_millis() as u64,
+            "decompress_ms": benchmark.decompress_time.as_millis() as u64,
+        }));
+    }
+
+    // Print or emit what `inspect` found in an archive
+    fn inspection(&self, archive: &Path, inspection: &Inspection) {
+        if self.json.is_none() {
+            print_inspection(archive, inspection);
+            return;
+        }
+        self.event(json!({
+            "event": "inspection",
+            "archive": archive,
+            "inspection": inspection,
+        }));
+    }
+
+    // Print or emit how two bundles relate
+    fn diff(&self, first: &Path, second: &Path, diff: &BundleDiff) {
+        if self.json.is_some() {
+            self.event(json!({
+                "event": "diff",
+                "first": first,
+                "second": second,
+                "diff": diff,
+                "handles_agree": diff.handles_agree(),
+            }));
+            return;
+        }
+        let describe = |relation: Relation| match relation {
+            Relation::Equivalent => "equivalent".to_string(),
+            Relation::FirstIsPrefix => {
+                format!("{} is a prefix of {}", first.display(), second.display())
+            }
+            Relation::SecondIsPrefix => {
+                format!("{} is a prefix of {}", second.display(), first.display())
+            }
+            Relation::Unrelated => "unrelated".to_string(),
+        };
+        self.say(&format!(
+            "  Public parameters: nu = {} and nu = {}, {}",
+            diff.first_nu,
+            diff.second_nu,
+            describe(diff.relation)
+        ));
+        if let Some(difference) = &diff.first_difference {
+            self.say(&format!(
+                "    First difference: {}[{}] in {}",
+                difference.vector, difference.index, difference.group
+            ));
+        }
+        let handles = &diff.blitzar_handles;
+        self.say(&format!(
+            "  Blitzar handles: {} and {} partitions, {}",
+            handles.first_partitions,
+            handles.second_partitions,
+            describe(handles.relation)
+        ));
+        if let Some(partition) = handles.first_difference {
+            self.say(&format!(
+                "    First difference: partition {}, covering generators {} to {}",
+                partition,
+                partition * BLITZAR_PARTITION_WIDTH,
+                (partition + 1) * BLITZAR_PARTITION_WIDTH - 1
+            ));
+        }
+        if !diff.handles_agree() {
+            self.say(
+                "  Warning: the handles and the parameters disagree, so at least one handle was \
+                 not built from the parameters in its archive",
+            );
+        }
+        self.say(&format!("The bundles are {}.", describe(diff.relation)));
+    }
+
+    // Print or emit the per-artifact sizes for one nu
+    fn sizes(&self, nu: usize, sizes: &ArtifactSizes, archive_name: &str, verifier_setup: bool) {
+        if self.json.is_none() {
+            print_sizes(nu, sizes, archive_name, verifier_setup);
+            return;
+        }
+        self.event(json!({
+            "event": "sizes",
+            "nu": nu,
+            "public_parameters": sizes.public_parameters,
+            "blitzar_handle": sizes.blitzar_handle,
+            "verifier_setup": verifier_setup.then_some(sizes.verifier_setup),
+            "total": sizes.total(verifier_setup),
+            "archive": archive_name,
+            "archive_max": tar_size(&[sizes.public_parameters, sizes.blitzar_handle]),
+            "peak_ram": sizes.peak_ram,
+        }));
+    }
+}
+
+fn main() -> ExitCode {
     // Parse command-line arguments
     let args = Args::parse();
+    let reporter = Reporter::new(args.output_format);
+    let command = args.command_name();
+    let start_time = Instant::now();
+    if let Err(e) = install_interrupt_handler(&reporter, command, start_time) {
+        eprintln!(
+            "Warning: could not install the SIGINT and SIGTERM handler: {}",
+            e
+        );
+    }
+    if reporter.json.is_none() {
+        print_banner();
+    }
+    reporter.event(json!({
+        "event": "start",
+        "command": command,
+        "version": env!("CARGO_PKG_VERSION"),
+    }));
 
-    // Convert the seed string to bytes and create a seeded RNG
-    let seed_bytes = "SpaceAndTime"
-        .bytes()
-        .chain(std::iter::repeat(0u8))
-        .take(32)
-        .collect::<Vec<_>>()
-        .try_into()
-        .expect("collection is guaranteed to contain 32 elements");
-    let mut rng = ChaCha20Rng::from_seed(seed_bytes);
-
-    // Calculate and print the estimated file size
-    let estimated_size_mb = estimated_file_size(args.nu);
-    println!(
-        "  Calculated public parameter size for nu = {:?} is {:.2} MB\n",
-        args.nu, estimated_size_mb
-    );
+    let result = match args.command {
+        Some(Command::Verify {
+            archive,
+            regenerate,
+            public_key,
+            spot_check,
+        }) => verify::verify_archive(
+            &archive,
+            regenerate,
+            spot_check,
+            public_key.as_deref(),
+            reporter.progress(),
+        )
+        .map(|()| reporter.say(&format!("{} verified successfully.", archive.display())))
+        .map_err(ParamGenError::Verification),
+        Some(Command::Inspect { archive }) => inspect::inspect_archive(&archive)
+            .map(|inspection| reporter.inspection(&archive, &inspection))
+            .map_err(|source| ParamGenError::Archive {
+                path: archive.clone(),
+                source,
+            }),
+        Some(Command::Diff { first, second }) => run_diff(&first, &second, &reporter),
+        Some(Command::Extend {
+            from,
+            to,
+            output,
+            force,
+        }) => run_resize(Resize::Extend, &from, to, output, force, &reporter),
+        Some(Command::Truncate {
+            from,
+            to,
+            output,
+            force,
+        }) => run_resize(Resize::Truncate, &from, to, output, force, &reporter),
+        Some(Command::BenchCompression {
+            archive,
+            compression_threads,
+        }) => run_bench_compression(&archive, compression_threads, &reporter),
+        Some(Command::Ceremony { action }) => run_ceremony(action, &reporter),
+        None => run_generate(args, &reporter),
+    };
 
-    // Use the `nu` value from the command-line argument
-    let public_parameters = PublicParameters::rand(args.nu, &mut rng);
+    let exit_code = match &result {
+        Ok(()) => 0,
+        Err(e) => {
+            if reporter.json.is_none() {
+                eprintln!("Error: {}", e);
+            }
+            reporter.event(json!({
+                "event": "error",
+                "message": e.to_string(),
+                "exit_code": e.exit_code(),
+            }));
+            e.exit_code()
+        }
+    };
+    reporter.event(json!({
+        "event": "summary",
+        "command": command,
+        "success": result.is_ok(),
+        "exit_code": exit_code,
+        "duration_ms": start_time.elapsed().as_millis() as u64,
+        "artifacts": reporter.artifacts.take(),
+    }));
+    ExitCode::from(exit_code)
+}
 
-    // Initialize a spinner using ProgressBar
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-    spinner.enable_steady_tick(Duration::from_millis(100));
+// On SIGINT or SIGTERM, stop the progress bar and report the partial outputs that were removed
+// before the process exits
+fn install_interrupt_handler(
+    reporter: &Reporter,
+    command: &'static str,
+    start_time: Instant,
+) -> std::io::Result<()> {
+    let terminal = reporter.terminal.clone();
+    let json = reporter.json.is_some();
+    interrupt::install(move |signal, removed| {
+        let exit_code = interrupt::exit_code(signal);
+        if !json {
+            terminal.abandon();
+            eprintln!("\nInterrupted by {}.", interrupt::signal_name(signal));
+            for path in removed {
+                eprintln!("  Removed partial output {}", path.display());
+            }
+            return;
+        }
+        // The reporter belongs to the interrupted thread, so the event is written directly
+        println!(
+            "{}",
+            json!({
+                "event": "interrupted",
+                "command": command,
+                "signal": interrupt::signal_name(signal),
+                "exit_code": exit_code,
+                "removed": removed,
+                "elapsed_ms": start_time.elapsed().as_millis() as u64,
+            })
+        );
+    })
+}
 
-    // Set initial message
-    spinner.set_message(
-        "Generating parameters for the SxT network.\nThis may take a long time, please wait...",
-    );
+fn run_generate(args: Args, reporter: &Reporter) -> Result<(), ParamGenError> {
+    let nus = args.nus();
+    let max_nu = *nus.last().expect("at least one nu is always given");
+    args.output.check_archive_settings()?;
+    args.output.check_archive_names()?;
 
-    let start_time = Instant::now();
+    // Resolve the seed
+    let seed = args.seed.resolve().map_err(ParamGenError::Seed)?;
+    match &args.hash_to_curve {
+        Some(dst) => reporter.say(&format!("  Hash-to-curve DST: {:?}", dst)),
+        None => reporter.say(&format!("  Seed ({}): {}", seed.source, seed.to_hex())),
+    }
 
-    // Spawn a thread to periodically update the spinner message with space facts
-    let spinner_clone = spinner.clone(); // Clone the spinner so we can use it in the thread
-    let fact_interval = Duration::from_secs(20); // Update the message every 5 seconds
-    thread::spawn(move || {
-        while !spinner_clone.is_finished() {
-            // Update the spinner message with a randomly selected space fact
-            spinner_clone.set_message("Generating public parameters for the SxT network. This may take a long time, please wait...\n".to_string());
-
-            // Sleep for the interval duration before updating again
-            thread::sleep(fact_interval);
-        }
-    });
-
-    let prover_setup = ProverSetup::from(&public_parameters);
-
-    // Stop the spinner once the operation is complete
-    spinner.finish_with_message("Prover setup complete.");
-    let duration = start_time.elapsed();
-    println!("Generated prover setup in {:.2?}", duration);
-
-    let result = public_parameters.save_to_file(Path::new("public_parameters.bin"));
-    match result {
-        Ok(_) => {
-            // Write the blitzar handle to a .bin file
-            let file_path = "blitzar_handle.bin";
-            let blitzar_handle = prover_setup.blitzar_handle();
-            blitzar_handle.write(file_path);
-
-            // Create a new spinner for the compression phase
-            let compression_spinner = ProgressBar::new_spinner();
-            compression_spinner.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} {msg}")
-                    .unwrap(),
-            );
-            compression_spinner.enable_steady_tick(Duration::from_millis(100));
-            compression_spinner.set_message("Setup complete! Compressing...");
+    let signing_key = args.output.signing_key()?;
 
-            // Start compression
-            let tar_gz_file_path = "dory-params.tar.gz";
-            let tar_gz_file = File::create(tar_gz_file_path).expect("Failed to create tar.gz file");
-            let enc = GzEncoder::new(tar_gz_file, Compression::default());
+    // Calculate and print the exact artifact sizes
+    let tiers: Vec<ArtifactSizes> = nus.iter().map(|&nu| ArtifactSizes::for_nu(nu)).collect();
+    for (&nu, sizes) in nus.iter().zip(&tiers) {
+        reporter.sizes(
+            nu,
+            sizes,
+            &args.archive_name(nu),
+            args.output.verifier_setup,
+        );
+    }
+    reporter.say(&format!(
+        "  Expected peak RAM during prover setup: {}\n",
+        format_mb(tiers.last().expect("one tier per nu").peak_ram)
+    ));
 
-            let mut tar_builder = Builder::new(enc);
+    let mut requirements = match tiers.as_slice() {
+        [sizes] => Requirements::new(sizes, args.output.verifier_setup),
+        tiers => Requirements::for_tiers(tiers, args.output.verifier_setup),
+    };
+    if args.output.keep_intermediates {
+        requirements = requirements.keeping_intermediates(&tiers, args.output.verifier_setup);
+    }
+    let work_dir_path = args.work_dir.as_deref().or(args.resume.as_deref());
+    if work_dir_path.is_some() {
+        requirements = requirements.checkpointing(&tiers);
+    }
+    check_resources(
+        &requirements,
+        &args.output.out_dir,
+        work_dir_path,
+        args.force,
+        reporter,
+    )?;
 
-            // Add both files to the tarball
-            tar_builder
-                .append_path("public_parameters.bin")
-                .expect("Failed to add public_parameters.bin to the tar file");
-            tar_builder
-                .append_path("blitzar_handle.bin")
-                .expect("Failed to add blitzar_handle.bin to the tar file");
+    let metadata = BundleMetadata::for_generation(max_nu, &seed, args.hash_to_curve.as_deref());
+    let mut work_dir = match (&args.work_dir, &args.resume) {
+        (Some(dir), _) => Some(WorkDir::create(dir, metadata)),
+        (None, Some(dir)) => Some(WorkDir::resume(dir, &metadata)),
+        (None, None) => None,
+    }
+    .transpose()
+    .map_err(ParamGenError::Checkpoint)?;
+    if let Some(work_dir) = &work_dir {
+        reporter.say(&format!(
+            "  Checkpoints: {}\n",
+            work_dir.path().join(CHECKPOINT_FILE).display()
+        ));
+    }
 
-            // Finalize the tar archive and compression
-            tar_builder
-                .finish()
-                .expect("Failed to finalize the tar.gz file");
+    // Generate once for the largest nu; every smaller tier is a prefix of it
+    let progress = reporter.progress();
+    let resumed = work_dir
+        .as_ref()
+        .map(WorkDir::load_public_parameters)
+        .transpose()
+        .map_err(ParamGenError::Checkpoint)?
+        .flatten();
+    let public_parameters = match resumed {
+        Some(public_parameters) => {
+            reporter.say("Resumed the generated parameters from their checkpoint");
+            public_parameters
+        }
+        None => {
+            let public_parameters = match &args.hash_to_curve {
+                Some(dst) => generate_hash_to_curve(max_nu, dst, progress)?,
+                None => generate(max_nu, &seed, progress)?,
+            };
+            if let Some(work_dir) = &mut work_dir {
+                work_dir
+                    .save_public_parameters(&public_parameters, progress)
+                    .map_err(ParamGenError::Checkpoint)?;
+            }
+            public_parameters
+        }
+    };
+    let opts = BundleOptions {
+        hash_to_curve_dst: args.hash_to_curve,
+        ..args.output.into_bundle_options(seed, signing_key)
+    };
+    let output = package_tiers(public_parameters, &nus, opts, work_dir.as_mut(), progress)?;
+    for bundle in &output.bundles {
+        report_bundle(bundle, reporter);
+    }
+    if let Some(index_path) = &output.index {
+        // The index is small, so hashing it back is cheap
+        let entry =
+            ManifestEntry::for_file(index_path).map_err(|source| ParamGenError::Serialization {
+                path: index_path.clone(),
+                source,
+            })?;
+        reporter.artifact(index_path, &entry);
+    }
+    Ok(())
+}
 
-            // Stop the compression spinner
-            compression_spinner.finish_with_message("Compression complete.");
+// Refuse to start a long run that is bound to fail for lack of disk or memory
+fn check_resources(
+    requirements: &Requirements,
+    out_dir: &Path,
+    work_dir: Option<&Path>,
+    force: bool,
+    reporter: &Reporter,
+) -> Result<(), ParamGenError> {
+    std::fs::create_dir_all(out_dir).map_err(|source| ParamGenError::OutputDir {
+        path: out_dir.to_path_buf(),
+        source,
+    })?;
+    let ResourceCheck {
+        shortfalls,
+        warnings,
+    } = requirements.check(out_dir, work_dir);
+    for warning in &warnings {
+        reporter.say(&format!("  Warning: {}", warning));
+    }
+    if !shortfalls.is_empty() {
+        if !force {
+            eprintln!("Aborting before generation. Pass --force to run anyway.");
+            return Err(ParamGenError::InsufficientResources(shortfalls));
+        }
+        for shortfall in &shortfalls {
+            eprintln!("  Insufficient resources: {}", shortfall);
+        }
+        eprintln!("Continuing anyway because --force was given.\n");
+    }
+    Ok(())
+}
+
+// How `run_resize` derives a bundle from an existing archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resize {
+    Extend,
+    Truncate,
+}
+
+// Write a bundle for `to` derived from the prover archive `from`, rebuilding every derived
+// artifact and recording `from` as the parent in the new manifests
+fn run_resize(
+    resize: Resize,
+    from: &Path,
+    to: usize,
+    output: OutputArgs,
+    force: bool,
+    reporter: &Reporter,
+) -> Result<(), ParamGenError> {
+    output.check_archive_settings()?;
+    output.check_archive_names()?;
+    let signing_key = output.signing_key()?;
+    let sizes = ArtifactSizes::for_nu(to);
+    let (archive_name, verifier_archive_name) = output.resized_archive_names(to);
+    reporter.sizes(to, &sizes, &archive_name, output.verifier_setup);
+    reporter.say(&format!(
+        "  Expected peak RAM during prover setup: {}\n",
+        format_mb(sizes.peak_ram)
+    ));
+    let mut requirements = Requirements::new(&sizes, output.verifier_setup);
+    if output.keep_intermediates {
+        requirements = requirements.keeping_intermediates(&[sizes], output.verifier_setup);
+    }
+    check_resources(&requirements, &output.out_dir, None, force, reporter)?;
+
+    let parent = ManifestEntry::for_file(from).map_err(|source| ParamGenError::Archive {
+        path: from.to_path_buf(),
+        source,
+    })?;
+    let (public_parameters, metadata) = load_public_parameters(from)?;
+    let metadata = metadata.ok_or_else(|| {
+        ParamGenError::Generation(format!(
+            "{} has no {}, so how it was generated is unknown",
+            from.display(),
+            METADATA_FILE
+        ))
+    })?;
+    let parameters = RawParameters::from_public_parameters(&public_parameters)
+        .map_err(|e| ParamGenError::Generation(e.to_string()))?;
+    drop(public_parameters);
+
+    let start_time = Instant::now();
+    let resized = match resize {
+        Resize::Extend => {
+            reporter.say(&format!(
+                "Extending {} from nu = {} to nu = {}",
+                from.display(),
+                parameters.nu(),
+                to
+            ));
+            extend::extend(&parameters, &metadata, to, reporter.progress())
+        }
+        Resize::Truncate => {
+            reporter.say(&format!(
+                "Truncating {} from nu = {} to nu = {}",
+                from.display(),
+                parameters.nu(),
+                to
+            ));
+            parameters.truncate(to).ok_or_else(|| {
+                format!(
+                    "the parameters only support nu = {}, cannot truncate to {}",
+                    parameters.nu(),
+                    to
+                )
+            })
+        }
+    };
+    let public_parameters = resized
+        .and_then(|resized| resized.to_public_parameters().map_err(|e| e.to_string()))
+        .map_err(ParamGenError::Generation)?;
+    reporter.say(&format!(
+        "Derived nu = {} in {:.2?}",
+        to,
+        start_time.elapsed()
+    ));
+
+    // Hashed generators record no seed, and the bundle's seed goes unused for them
+    let seed = match metadata.hash_to_curve_dst {
+        Some(_) => Seed::default(),
+        None => metadata.seed().map_err(ParamGenError::Seed)?,
+    };
+    let opts = BundleOptions {
+        archive_name,
+        verifier_archive_name,
+        hash_to_curve_dst: metadata.hash_to_curve_dst,
+        parent_archive: Some(parent),
+        ..output.into_bundle_options(seed, signing_key)
+    };
+    let output = package(&public_parameters, &opts, None, None, reporter.progress())?;
+    report_bundle(&output, reporter);
+    Ok(())
+}
+
+// Compare two prover archives and report how they relate
+fn run_diff(first: &Path, second: &Path, reporter: &Reporter) -> Result<(), ParamGenError> {
+    reporter.say(&format!(
+        "Comparing {} with {}",
+        first.display(),
+        second.display()
+    ));
+    let diff = diff::diff_bundles(first, second, reporter.progress()).map_err(|e| {
+        ParamGenError::Archive {
+            path: e.path,
+            source: e.source,
+        }
+    })?;
+    reporter.diff(first, second, &diff);
+    if !diff.is_equivalent() {
+        return Err(ParamGenError::Difference(format!(
+            "{} and {} are not equivalent",
+            first.display(),
+            second.display()
+        )));
+    }
+    Ok(())
+}
 
-            // Remove the .bin files after archiving
-            fs::remove_file("public_parameters.bin")
-                .expect("Failed to remove public_parameters.bin");
-            fs::remove_file(file_path).expect("Failed to remove blitzar_handle.bin");
+// Recompress `archive` in every format and report each one
+fn run_bench_compression(
+    archive: &Path,
+    threads: Option<u64>,
+    reporter: &Reporter,
+) -> Result<(), ParamGenError> {
+    let threads = compression_options(ArchiveFormat::TarGz, threads).threads;
+    reporter.say(&format!(
+        "Recompressing the members of {} on {} threads",
+        archive.display(),
+        threads
+    ));
+    let benchmarks =
+        bench::bench_compression(archive, threads, reporter.progress()).map_err(|source| {
+            ParamGenError::Archive {
+                path: archive.to_path_buf(),
+                source,
+            }
+        })?;
+    if let Some(benchmark) = benchmarks.first() {
+        reporter.say(&format!(
+            "  Uncompressed tar: {}",
+            format_mb(benchmark.uncompressed_size)
+        ));
+    }
+    for benchmark in &benchmarks {
+        reporter.benchmark(benchmark);
+    }
+    Ok(())
+}
 
-            println!("Temporary .bin files removed.");
+// Print an archive's description, with the manifest as it is stored
+fn print_inspection(archive: &Path, inspection: &Inspection) {
+    println!(
+        "{} ({}, {})",
+        archive.display(),
+        inspection.format,
+        format_mb(inspection.size)
+    );
+    println!("  Members:");
+    for member in &inspection.members {
+        println!(
+            "    {:<24} {:>12}  {}",
+            member.name,
+            format_mb(member.size),
+            member.sha256
+        );
+    }
+    if let Some(public_parameters) = &inspection.public_parameters {
+        println!(
+            "  Public parameters: nu = {}, Γ_1 and Γ_2 of {} generators each",
+            public_parameters.nu, public_parameters.vector_length
+        );
+    }
+    if let Some(handle) = &inspection.blitzar_handle {
+        println!(
+            "  Blitzar handle: {} partitions of {} generators with {} precomputed points each, \
+             covering {} generators",
+            handle.partitions,
+            handle.partition_width,
+            handle.points_per_partition,
+            handle.generators
+        );
+    }
+    if let Some(metadata) = &inspection.metadata {
+        match &metadata.hash_to_curve_dst {
+            Some(dst) => println!("  Generated by hash-to-curve under DST {:?}", dst),
+            None => println!(
+                "  Generated from {} ({})",
+                metadata.derivation(),
+                metadata
+                    .seed_source
+                    .as_deref()
+                    .unwrap_or("source not recorded")
+            ),
+        }
+    }
+    match &inspection.manifest {
+        Some(manifest) => {
+            let signed = if inspection.signed {
+                "signed"
+            } else {
+                "unsigned"
+            };
+            println!(
+                "  Manifest ({}): generator {}, proof-of-sql {}, blitzar {}",
+                signed,
+                manifest.generator_version,
+                manifest.proof_of_sql_version,
+                manifest.blitzar_version
+            );
+            let json = serde_json::to_string_pretty(manifest).unwrap_or_default();
+            for line in json.lines() {
+                println!("    {}", line);
+            }
+        }
+        None => println!("  No manifest"),
+    }
+    for warning in &inspection.warnings {
+        println!("  Warning: {}", warning);
+    }
+}
+
+// Print the per-artifact sizes for one nu
+fn print_sizes(nu: usize, sizes: &ArtifactSizes, archive_name: &str, verifier_setup: bool) {
+    println!("  Artifact sizes for nu = {}:", nu);
+    println!(
+        "    public_parameters.bin  {}",
+        format_mb(sizes.public_parameters)
+    );
+    println!(
+        "    blitzar_handle.bin     {}",
+        format_mb(sizes.blitzar_handle)
+    );
+    if verifier_setup {
+        println!(
+            "    verifier_setup.bin     {}",
+            format_mb(sizes.verifier_setup)
+        );
+    }
+    println!(
+        "    total                  {}",
+        format_mb(sizes.total(verifier_setup))
+    );
+    println!(
+        "    {:<22} up to {}",
+        archive_name,
+        format_mb(tar_size(&[sizes.public_parameters, sizes.blitzar_handle]))
+    );
+}
+
+// Report the signing key and every archive of a packaged bundle
+fn report_bundle(output: &BundleOutput, reporter: &Reporter) {
+    if let Some(public_key) = &output.public_key {
+        reporter.say(&format!("Signed manifests with public key {}", public_key));
+    }
+    for (archive, digest) in output.archives.iter().zip(&output.digests) {
+        reporter.artifact(archive, digest);
+    }
+    for dir in &output.intermediates {
+        reporter.say(&format!("Intermediate files kept in {}", dir.display()));
+    }
+}
+
+fn run_ceremony(action: CeremonyCommand, reporter: &Reporter) -> Result<(), ParamGenError> {
+    match action {
+        CeremonyCommand::Init { dir, nu, seed } => {
+            let seed = seed.resolve().map_err(ParamGenError::Seed)?;
+            reporter.say(&format!("  Seed ({}): {}", seed.source, seed.to_hex()));
+            ceremony::init(&dir, nu, &seed).map_err(ParamGenError::Ceremony)?;
+            reporter.say(&format!(
+                "Started a ceremony for nu = {} in {}. Pass the directory to the first participant.",
+                nu,
+                dir.display()
+            ));
+        }
+        CeremonyCommand::Contribute { dir, name } => {
+            let contribution =
+                ceremony::contribute(&dir, &name).map_err(ParamGenError::Ceremony)?;
+            reporter.say(&format!(
+                "Recorded contribution {} by {}: {} ({})",
+                contribution.index,
+                contribution.participant,
+                contribution.state.file,
+                contribution.state.sha256
+            ));
+            reporter.say("Your secrets were never written to disk. Pass the directory on.");
+        }
+        CeremonyCommand::Verify { dir } => {
+            let transcript = ceremony::verify(&dir).map_err(ParamGenError::Ceremony)?;
+            for contribution in &transcript.contributions {
+                reporter.say(&format!(
+                    "  {:>4}  {}  {}",
+                    contribution.index, contribution.state.sha256, contribution.participant
+                ));
+            }
+            reporter.say(&format!(
+                "All {} contributions to {} verified successfully.",
+                transcript.contributions.len(),
+                dir.display()
+            ));
+        }
+        CeremonyCommand::Finalize { dir, output } => {
+            output.check_archive_settings()?;
+            output.check_archive_names()?;
+            let signing_key = output.signing_key()?;
+            let (public_parameters, transcript) =
+                ceremony::finalize(&dir).map_err(ParamGenError::Ceremony)?;
+            let (_, fingerprint) = manifest::sha256_file(&dir.join(ceremony::TRANSCRIPT_FILE))
+                .map_err(|e| ParamGenError::Ceremony(e.to_string()))?;
+            let seed = Seed {
+                bytes: Seed::from_hex(&transcript.initial_seed)
+                    .map_err(ParamGenError::Ceremony)?
+                    .bytes,
+                source: SeedSource::Ceremony(fingerprint),
+            };
+            reporter.say(&format!(
+                "Verified {} contributions, packaging the final state.",
+                transcript.contributions.len()
+            ));
+            let output = package(
+                &public_parameters,
+                &output.into_bundle_options(seed, signing_key),
+                None,
+                None,
+                reporter.progress(),
+            )?;
+            report_bundle(&output, reporter);
         }
-        Err(_) => println!("Failed to save parameters, aborting."),
     }
+    Ok(())
 }
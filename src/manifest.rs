@@ -0,0 +1,188 @@
+use crate::{hex, seed::Seed};
+use ring::{
+    digest::{self, Context, SHA256},
+    signature::{self, Ed25519KeyPair, KeyPair, UnparsedPublicKey},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::Path,
+};
+
+/// File name of the manifest entry inside the archive.
+pub const MANIFEST_FILE: &str = "manifest.json";
+/// File name of the hex-encoded Ed25519 signature over the manifest.
+pub const SIGNATURE_FILE: &str = "manifest.sig";
+
+/// Size and digest of a single archive member.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl ManifestEntry {
+    /// Hash the file at `path`, naming the entry by its file name.
+    pub fn for_file(path: &Path) -> std::io::Result<Self> {
+        let (size, sha256) = sha256_file(path)?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self { name, size, sha256 })
+    }
+}
+
+/// Lists every member of a bundle along with the versions that produced it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// The `nu` the public parameters were generated for
+    pub nu: usize,
+    /// SHA-256 of the 32-byte RNG seed, absent when the generators were hashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed_fingerprint: Option<String>,
+    /// Version of this generator
+    pub generator_version: String,
+    /// Version of proof-of-sql used to generate the parameters
+    pub proof_of_sql_version: String,
+    /// Version of blitzar used to write the handle
+    pub blitzar_version: String,
+    /// Domain-separation tag the generators were hashed to the curve with, if not seeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_to_curve_dst: Option<String>,
+    /// The archive these parameters were extended or truncated from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_archive: Option<ManifestEntry>,
+    pub members: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Build a manifest by hashing each of `members` inside `dir`.
+    pub fn new(nu: usize, seed: &Seed, dir: &Path, members: &[&str]) -> Result<Self, String> {
+        let members = members
+            .iter()
+            .map(|name| {
+                let path = dir.join(name);
+                let (size, sha256) = sha256_file(&path)
+                    .map_err(|e| format!("failed to hash {}: {}", path.display(), e))?;
+                Ok(ManifestEntry {
+                    name: name.to_string(),
+                    size,
+                    sha256,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self::from_members(nu, seed, members))
+    }
+
+    /// Build a manifest from members already hashed, e.g. while they were archived.
+    pub fn from_members(nu: usize, seed: &Seed, members: Vec<ManifestEntry>) -> Self {
+        Self {
+            nu,
+            seed_fingerprint: Some(sha256_hex(&seed.bytes)),
+            generator_version: env!("CARGO_PKG_VERSION").to_string(),
+            proof_of_sql_version: env!("PROOF_OF_SQL_VERSION").to_string(),
+            blitzar_version: env!("BLITZAR_VERSION").to_string(),
+            hash_to_curve_dst: None,
+            parent_archive: None,
+            members,
+        }
+    }
+
+    /// Check that every listed member in `dir` has the recorded size and digest.
+    pub fn check_members(&self, dir: &Path) -> Result<(), String> {
+        for entry in &self.members {
+            let path = dir.join(&entry.name);
+            let (size, sha256) =
+                sha256_file(&path).map_err(|e| format!("failed to hash {}: {}", entry.name, e))?;
+            if size != entry.size {
+                return Err(format!(
+                    "{} is {} bytes, manifest records {}",
+                    entry.name, size, entry.size
+                ));
+            }
+            if sha256 != entry.sha256 {
+                return Err(format!(
+                    "{} has SHA-256 {}, manifest records {}",
+                    entry.name, sha256, entry.sha256
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Hex SHA-256 digest of `bytes`.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(digest::digest(&SHA256, bytes).as_ref())
+}
+
+/// Stream a file through SHA-256, returning its size and hex digest.
+pub fn sha256_file(path: &Path) -> std::io::Result<(u64, String)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut context = Context::new(&SHA256);
+    let mut buf = vec![0u8; 1 << 20];
+    let mut size = 0u64;
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => {
+                context.update(&buf[..n]);
+                size += n as u64;
+            }
+        }
+    }
+    Ok((size, hex::encode(context.finish().as_ref())))
+}
+
+/// Load an Ed25519 signing key from a PKCS#8 DER file or a raw 32-byte seed file.
+pub fn load_signing_key(path: &Path) -> Result<Ed25519KeyPair, String> {
+    let bytes =
+        fs::read(path).map_err(|e| format!("failed to read key {}: {}", path.display(), e))?;
+    let key_pair = if bytes.len() == 32 {
+        Ed25519KeyPair::from_seed_unchecked(&bytes)
+    } else {
+        Ed25519KeyPair::from_pkcs8_maybe_unchecked(&bytes)
+    };
+    key_pair.map_err(|e| format!("invalid Ed25519 key {}: {}", path.display(), e))
+}
+
+/// Sign `message`, returning the hex-encoded signature.
+pub fn sign(key_pair: &Ed25519KeyPair, message: &[u8]) -> String {
+    hex::encode(key_pair.sign(message).as_ref())
+}
+
+/// Hex encoding of the public half of a signing key.
+pub fn public_key_hex(key_pair: &Ed25519KeyPair) -> String {
+    hex::encode(key_pair.public_key().as_ref())
+}
+
+/// Check a hex-encoded signature over `message` against a public key file.
+///
+/// The public key file may hold either 32 raw bytes or their hex encoding.
+pub fn verify_signature(
+    public_key_path: &Path,
+    message: &[u8],
+    signature_hex: &str,
+) -> Result<(), String> {
+    let bytes = fs::read(public_key_path).map_err(|e| {
+        format!(
+            "failed to read public key {}: {}",
+            public_key_path.display(),
+            e
+        )
+    })?;
+    let public_key = if bytes.len() == 32 {
+        bytes
+    } else {
+        hex::decode(String::from_utf8_lossy(&bytes).trim())
+            .map_err(|e| format!("invalid public key: {}", e))?
+    };
+    let signature =
+        hex::decode(signature_hex.trim()).map_err(|e| format!("invalid signature: {}", e))?;
+    UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(message, &signature)
+        .map_err(|_| "manifest signature does not match the public key".to_string())
+}
@@ -0,0 +1,96 @@
+use crate::seed::{Seed, SeedSource};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// File name of the metadata entry inside the archive.
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Describes how a parameter bundle was generated.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BundleMetadata {
+    /// The `nu` the public parameters were generated for
+    pub nu: usize,
+    /// Hex encoding of the 32-byte ChaCha20 seed, absent when the generators were hashed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<String>,
+    /// Human-readable description of how the seed was derived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed_source: Option<String>,
+    /// Domain-separation tag the generators were hashed to the curve with, replacing the seed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_to_curve_dst: Option<String>,
+}
+
+impl BundleMetadata {
+    pub fn new(nu: usize, seed: &Seed) -> Self {
+        Self {
+            nu,
+            seed: Some(seed.to_hex()),
+            seed_source: Some(seed.source.to_string()),
+            hash_to_curve_dst: None,
+        }
+    }
+
+    /// Metadata for generators hashed to the curve under `dst`, which no seed takes part in.
+    pub fn hash_to_curve(nu: usize, dst: &str) -> Self {
+        Self {
+            nu,
+            seed: None,
+            seed_source: None,
+            hash_to_curve_dst: Some(dst.to_string()),
+        }
+    }
+
+    /// Metadata for parameters drawn from `seed`, or hashed under `dst` if one is given.
+    pub fn for_generation(nu: usize, seed: &Seed, dst: Option<&str>) -> Self {
+        match dst {
+            Some(dst) => Self::hash_to_curve(nu, dst),
+            None => Self::new(nu, seed),
+        }
+    }
+
+    /// The recorded seed, keeping its ceremony origin if it has one.
+    pub fn seed(&self) -> Result<Seed, String> {
+        let hex = self
+            .seed
+            .as_deref()
+            .ok_or("the generators were hashed to the curve, not drawn from a seed")?;
+        let seed = Seed::from_hex(hex)?;
+        let source = match self
+            .seed_source
+            .as_deref()
+            .and_then(|source| source.strip_prefix("ceremony transcript "))
+        {
+            Some(transcript) => SeedSource::Ceremony(transcript.to_string()),
+            None => seed.source,
+        };
+        Ok(Seed { source, ..seed })
+    }
+
+    /// Where the parameters came from, if they are the final state of a ceremony rather than
+    /// seeded or hashed.
+    pub fn ceremony_source(&self) -> Option<&str> {
+        self.seed_source
+            .as_deref()
+            .filter(|source| source.starts_with("ceremony"))
+    }
+
+    /// How the generators were derived, e.g. `seed 00ab...` or `hash-to-curve DST "..."`.
+    pub fn derivation(&self) -> String {
+        match (&self.hash_to_curve_dst, &self.seed) {
+            (Some(dst), _) => format!("hash-to-curve DST {:?}", dst),
+            (None, Some(seed)) => format!("seed {}", seed),
+            (None, None) => "an unrecorded seed".to_string(),
+        }
+    }
+
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    pub fn load_from_file(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
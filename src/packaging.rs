@@ -0,0 +1,240 @@
+//! Packaging the bundles of a run, one tier at a time, resuming from a work directory's
+//! checkpoints where one is given.
+
+use crate::{
+    archive::archive_name_for_nu,
+    bundle::{prover_setup, write_bundle, BundleOptions, BundleOutput, TierHandles},
+    checkpoint::WorkDir,
+    error::ParamGenError,
+    index::{BundleIndex, INDEX_FILE},
+    manifest,
+    progress::Progress,
+    raw_parameters::RawParameters,
+    sizes::nu_of,
+};
+use proof_of_sql::proof_primitive::dory::{ProverSetup, PublicParameters};
+use std::path::PathBuf;
+
+/// The bundles written by [`package_tiers`].
+pub struct TieredOutput {
+    /// One bundle per tier, in increasing order of `nu`
+    pub bundles: Vec<BundleOutput>,
+    /// The index listing every bundle, written only when there are several tiers
+    pub index: Option<PathBuf>,
+}
+
+/// The name `archive_name` takes for `nu` in a run over `tiers` tiers: as given for a single
+/// tier and suffixed by `nu` otherwise.
+pub fn tier_archive_name(archive_name: &str, nu: usize, tiers: usize) -> String {
+    if tiers == 1 {
+        archive_name.to_string()
+    } else {
+        archive_name_for_nu(archive_name, nu)
+    }
+}
+
+/// The name of an archive derived for `nu` from an existing bundle: `archive_name` as given if
+/// one was requested, and `default_name` suffixed by `nu` otherwise.
+pub fn resized_archive_name(archive_name: Option<&str>, default_name: &str, nu: usize) -> String {
+    match archive_name {
+        Some(archive_name) => archive_name.to_string(),
+        None => archive_name_for_nu(default_name, nu),
+    }
+}
+
+/// Package one bundle per `nu` in `nus`, given in increasing order, cutting each tier's
+/// parameters from `public_parameters`, which were generated for the largest one.
+///
+/// The archives are named by [`tier_archive_name`]. With several tiers, only the largest
+/// tier's blitzar handle is built from scratch, and a [`BundleIndex`] of every archive written
+/// is saved as [`INDEX_FILE`] in the output directory.
+pub fn package_tiers(
+    public_parameters: PublicParameters,
+    nus: &[usize],
+    mut opts: BundleOptions,
+    mut work_dir: Option<&mut WorkDir>,
+    progress: &dyn Progress,
+) -> Result<TieredOutput, ParamGenError> {
+    if nus.len() == 1 {
+        let output = package(&public_parameters, &opts, work_dir, None, progress)?;
+        return Ok(TieredOutput {
+            bundles: vec![output],
+            index: None,
+        });
+    }
+
+    let parameters = RawParameters::from_public_parameters(&public_parameters)
+        .map_err(|e| ParamGenError::Generation(e.to_string()))?;
+    drop(public_parameters);
+    let mut tiers =
+        TierHandles::new_in(&opts.out_dir).map_err(|source| ParamGenError::OutputDir {
+            path: opts.out_dir.clone(),
+            source,
+        })?;
+    let archive_name = opts.archive_name.clone();
+    let verifier_archive_name = opts.verifier_archive_name.clone();
+    let mut bundles = Vec::with_capacity(nus.len());
+    let mut index = BundleIndex::default();
+    // Only the largest tier's blitzar handle is precomputed, so it is packaged first
+    for &nu in nus.iter().rev() {
+        progress.message(&format!("\nPackaging nu = {}", nu));
+        let public_parameters = parameters
+            .truncate(nu)
+            .ok_or_else(|| {
+                ParamGenError::Generation(format!(
+                    "the parameters only support nu = {}, cannot package nu = {}",
+                    parameters.nu(),
+                    nu
+                ))
+            })?
+            .to_public_parameters()
+            .map_err(|e| ParamGenError::Generation(e.to_string()))?;
+        opts.archive_name = tier_archive_name(&archive_name, nu, nus.len());
+        opts.verifier_archive_name = tier_archive_name(&verifier_archive_name, nu, nus.len());
+        let output = package(
+            &public_parameters,
+            &opts,
+            work_dir.as_deref_mut(),
+            Some(&mut tiers),
+            progress,
+        )?;
+        index.add(nu, output.digests.clone());
+        bundles.push(output);
+    }
+    bundles.reverse();
+    index.bundles.sort_by_key(|entry| entry.nu);
+    let index_path = opts.out_dir.join(INDEX_FILE);
+    index
+        .save_to_file(&index_path)
+        .map_err(|source| ParamGenError::Serialization {
+            path: index_path.clone(),
+            source,
+        })?;
+    Ok(TieredOutput {
+        bundles,
+        index: Some(index_path),
+    })
+}
+
+/// Build the prover setup for `public_parameters` and write the archives described by `opts`,
+/// skipping whatever `work_dir` records as already done.
+///
+/// With `tiers`, the prover setup is cut from a larger tier's blitzar handle where one was
+/// kept, and its own handle is kept for the smaller tiers otherwise.
+pub fn package(
+    public_parameters: &PublicParameters,
+    opts: &BundleOptions,
+    work_dir: Option<&mut WorkDir>,
+    mut tiers: Option<&mut TierHandles>,
+    progress: &dyn Progress,
+) -> Result<BundleOutput, ParamGenError> {
+    let nu = nu_of(public_parameters);
+    let Some(work_dir) = work_dir else {
+        let prover_setup = match tiers {
+            Some(tiers) => match cut_prover_setup(tiers, public_parameters, progress)? {
+                Some(prover_setup) => prover_setup,
+                None => {
+                    let blitzar_handle =
+                        prover_setup(public_parameters, progress)?.blitzar_handle();
+                    tiers.keep(nu, &blitzar_handle, progress)?;
+                    ProverSetup::from_public_parameters_and_blitzar_handle(
+                        public_parameters,
+                        blitzar_handle,
+                    )
+                }
+            },
+            None => prover_setup(public_parameters, progress)?,
+        };
+        return write_bundle(prover_setup, public_parameters, opts, progress);
+    };
+
+    let mut archives = vec![opts.out_dir.join(&opts.archive_name)];
+    if opts.verifier_setup {
+        archives.push(opts.out_dir.join(&opts.verifier_archive_name));
+    }
+    if let Some(digests) = work_dir.finished_archives(nu, &archives) {
+        progress.message(&format!(
+            "The archives for nu = {} were already written, skipping them",
+            nu
+        ));
+        offer_checkpointed_handle(work_dir, tiers, nu)?;
+        return Ok(BundleOutput {
+            archives,
+            digests,
+            intermediates: Vec::new(),
+            public_key: opts.signing_key.as_ref().map(manifest::public_key_hex),
+        });
+    }
+
+    let resumed = work_dir
+        .load_prover_setup(public_parameters)
+        .map_err(ParamGenError::Checkpoint)?;
+    let prover_setup = match resumed {
+        Some(prover_setup) => {
+            progress.message(&format!(
+                "Resumed the prover setup for nu = {} from its checkpointed blitzar handle",
+                nu
+            ));
+            prover_setup
+        }
+        None => {
+            let cut = match tiers.as_deref_mut() {
+                Some(tiers) => cut_prover_setup(tiers, public_parameters, progress)?,
+                None => None,
+            };
+            // Taking the handle consumes the setup, so it is rebuilt around the saved handle
+            let blitzar_handle = match cut {
+                Some(prover_setup) => prover_setup.blitzar_handle(),
+                None => prover_setup(public_parameters, progress)?.blitzar_handle(),
+            };
+            work_dir
+                .save_blitzar_handle(nu, &blitzar_handle, progress)
+                .map_err(ParamGenError::Checkpoint)?;
+            ProverSetup::from_public_parameters_and_blitzar_handle(
+                public_parameters,
+                blitzar_handle,
+            )
+        }
+    };
+    // The checkpointed handle doubles as the one smaller tiers are cut from
+    offer_checkpointed_handle(work_dir, tiers, nu)?;
+    let output = write_bundle(prover_setup, public_parameters, opts, progress)?;
+    work_dir
+        .record_archives(nu, &output)
+        .map_err(ParamGenError::Checkpoint)?;
+    Ok(output)
+}
+
+// The prover setup for one tier of a multi-tier run, cut from a larger tier's handle if it can be
+fn cut_prover_setup<'a>(
+    tiers: &TierHandles,
+    public_parameters: &'a PublicParameters,
+    progress: &dyn Progress,
+) -> Result<Option<ProverSetup<'a>>, ParamGenError> {
+    let prover_setup = tiers.cut(public_parameters, progress)?;
+    if prover_setup.is_some() {
+        progress.message(&format!(
+            "Cut the blitzar handle for nu = {} from the largest tier's handle",
+            nu_of(public_parameters)
+        ));
+    }
+    Ok(prover_setup)
+}
+
+// Let smaller tiers be cut from the checkpointed handle for `nu`
+fn offer_checkpointed_handle(
+    work_dir: &WorkDir,
+    tiers: Option<&mut TierHandles>,
+    nu: usize,
+) -> Result<(), ParamGenError> {
+    let Some(tiers) = tiers.filter(|tiers| !tiers.covers(nu)) else {
+        return Ok(());
+    };
+    if let Some(path) = work_dir
+        .blitzar_handle_path(nu)
+        .map_err(ParamGenError::Checkpoint)?
+    {
+        tiers.offer(nu, path);
+    }
+    Ok(())
+}
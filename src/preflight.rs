@@ -0,0 +1,208 @@
+use crate::sizes::{format_mb, tar_size, ArtifactSizes};
+use std::{
+    ffi::CString,
+    fs, io,
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::Path,
+};
+
+// Upper bound on the archives for one tier: the uncompressed tar of every member
+fn archives_size(sizes: &ArtifactSizes, with_verifier_setup: bool) -> u64 {
+    let mut archives = tar_size(&[sizes.public_parameters, sizes.blitzar_handle]);
+    if with_verifier_setup {
+        archives += tar_size(&[sizes.verifier_setup]);
+    }
+    archives
+}
+
+/// What [`Requirements::check`] found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceCheck {
+    /// A description of each resource that falls short
+    pub shortfalls: Vec<String>,
+    /// Resources that could not be measured, which are not counted as shortfalls
+    pub warnings: Vec<String>,
+}
+
+/// Disk space and memory a generation run needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Requirements {
+    /// The archives; artifacts are streamed into them, so an uncompressed archive is the worst case
+    pub disk: u64,
+    /// Peak memory while building the prover setup
+    pub ram: u64,
+    /// The checkpointed artifacts kept in the work directory, if there is one
+    pub checkpoints: u64,
+}
+
+impl Requirements {
+    pub fn new(sizes: &ArtifactSizes, with_verifier_setup: bool) -> Self {
+        Self {
+            disk: archives_size(sizes, with_verifier_setup),
+            ram: sizes.peak_ram,
+            checkpoints: 0,
+        }
+    }
+
+    /// Requirements for generating several `nu` tiers in one run.
+    ///
+    /// Every finished archive stays in the output directory, and the largest tier's generators
+    /// are held in memory for the whole run. The largest tier's blitzar handle is also kept on
+    /// disk, next to the prefix of it loaded for one smaller tier at a time.
+    pub fn for_tiers(tiers: &[ArtifactSizes], with_verifier_setup: bool) -> Self {
+        let archives: u64 = tiers
+            .iter()
+            .map(|sizes| archives_size(sizes, with_verifier_setup))
+            .sum();
+        let largest = tiers
+            .iter()
+            .max_by_key(|sizes| sizes.public_parameters)
+            .copied()
+            .unwrap_or(ArtifactSizes::for_nu(0));
+        let mut handles: Vec<u64> = tiers.iter().map(|sizes| sizes.blitzar_handle).collect();
+        handles.sort_unstable();
+        let kept_handles: u64 = handles.iter().rev().take(2).sum();
+        Self {
+            disk: archives + kept_handles,
+            ram: largest.peak_ram + largest.public_parameters,
+            checkpoints: 0,
+        }
+    }
+
+    /// Add room for an uncompressed copy of every tier's members kept next to its archives.
+    pub fn keeping_intermediates(self, tiers: &[ArtifactSizes], with_verifier_setup: bool) -> Self {
+        let kept: u64 = tiers
+            .iter()
+            .map(|sizes| sizes.total(with_verifier_setup))
+            .sum();
+        Self {
+            disk: self.disk + kept,
+            ..self
+        }
+    }
+
+    /// Add room for the checkpoints of a run over `tiers`: the largest tier's public parameters
+    /// and one blitzar handle per tier.
+    pub fn checkpointing(self, tiers: &[ArtifactSizes]) -> Self {
+        let public_parameters = tiers
+            .iter()
+            .map(|sizes| sizes.public_parameters)
+            .max()
+            .unwrap_or(0);
+        let handles: u64 = tiers.iter().map(|sizes| sizes.blitzar_handle).sum();
+        Self {
+            checkpoints: public_parameters + handles,
+            ..self
+        }
+    }
+
+    /// Compare against the filesystem holding `dir`, the one holding `work_dir` if checkpoints
+    /// are kept there, and the system's available memory.
+    ///
+    /// Whatever `work_dir` already holds counts towards its checkpoints, so a resumed run only
+    /// needs room for the ones still to be written.
+    pub fn check(&self, dir: &Path, work_dir: Option<&Path>) -> ResourceCheck {
+        let mut check = ResourceCheck::default();
+        let mut needs = vec![(dir, self.disk)];
+        if let Some(work_dir) = work_dir {
+            let checkpoints = self.checkpoints.saturating_sub(dir_size(work_dir));
+            // The work directory is only created once the run starts
+            let work_dir = existing_ancestor(work_dir);
+            if same_filesystem(dir, work_dir) {
+                needs[0].1 += checkpoints;
+            } else {
+                needs.push((work_dir, checkpoints));
+            }
+        }
+        for (dir, needed) in needs {
+            match available_disk(dir) {
+                Ok(available) if available < needed => check.shortfalls.push(format!(
+                    "{} of disk space is needed in {} but only {} is available",
+                    format_mb(needed),
+                    dir.display(),
+                    format_mb(available)
+                )),
+                Ok(_) => {}
+                Err(e) => check.warnings.push(format!(
+                    "could not check free disk space in {}: {}",
+                    dir.display(),
+                    e
+                )),
+            }
+        }
+        match available_memory() {
+            Ok(available) if available < self.ram => check.shortfalls.push(format!(
+                "{} of memory is needed but only {} is available",
+                format_mb(self.ram),
+                format_mb(available)
+            )),
+            Ok(_) => {}
+            Err(e) => check
+                .warnings
+                .push(format!("could not check available memory: {}", e)),
+        }
+        check
+    }
+}
+
+// The closest of `path` and its parents that exists
+fn existing_ancestor(path: &Path) -> &Path {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .unwrap_or(Path::new("."))
+}
+
+// Whether `a` and `b` are on one filesystem, assuming they are if either cannot be checked so
+// their needs are counted together
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => true,
+    }
+}
+
+// Total size of the files directly inside `dir`, or zero if it cannot be listed
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `dir`.
+pub fn available_disk(dir: &Path) -> io::Result<u64> {
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read after statvfs succeeds
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    Ok(stat.f_bavail * stat.f_frsize)
+}
+
+/// Bytes of memory available for new allocations, according to /proc/meminfo.
+pub fn available_memory() -> io::Result<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo")?;
+    parse_mem_available(&meminfo).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "MemAvailable not found in /proc/meminfo",
+        )
+    })
+}
+
+/// Extract the MemAvailable line (reported in kB) from the contents of /proc/meminfo.
+pub fn parse_mem_available(meminfo: &str) -> Option<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))
+        .and_then(|rest| rest.trim().strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
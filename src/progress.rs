@@ -0,0 +1,348 @@
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A long-running stage of producing a bundle.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Sampling or deriving the generators, counted in generators
+    Generators,
+    /// `ProverSetup::from`, which exposes no progress of its own
+    ProverSetup,
+    /// Writing `public_parameters.bin`, counted in bytes
+    PublicParameters,
+    /// Writing `blitzar_handle.bin`, counted in bytes
+    BlitzarHandle,
+    /// Deriving and writing the verifier setup, which exposes no progress of its own
+    VerifierSetup,
+    /// Adding the remaining members to an archive and finishing it, counted in member bytes
+    Archive,
+    /// Reading two bundles side by side, counted in member bytes from both
+    Comparison,
+}
+
+impl Phase {
+    /// Whether the phase is counted in bytes rather than generators.
+    pub fn counts_bytes(&self) -> bool {
+        matches!(
+            self,
+            Phase::PublicParameters | Phase::BlitzarHandle | Phase::Archive | Phase::Comparison
+        )
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Generators => "generating generators",
+            Phase::ProverSetup => "building prover setup",
+            Phase::PublicParameters => "writing public parameters",
+            Phase::BlitzarHandle => "writing blitzar handle",
+            Phase::VerifierSetup => "building verifier setup",
+            Phase::Archive => "writing archive",
+            Phase::Comparison => "comparing bundles",
+        })
+    }
+}
+
+/// Receives progress from the generation and packaging steps.
+///
+/// Phases run one at a time. `advance` may be called from several threads at once, so
+/// implementations should be cheap and internally synchronized.
+pub trait Progress: Sync {
+    /// A phase started, with its total size if it can be measured.
+    fn start(&self, phase: Phase, total: Option<u64>);
+
+    /// `amount` more units of the current phase are done.
+    fn advance(&self, amount: u64);
+
+    /// The compressed size of the archive being written so far.
+    fn compressed(&self, _bytes: u64) {}
+
+    /// The current phase is complete.
+    fn finish(&self);
+
+    /// A human-readable status line.
+    fn message(&self, _text: &str) {}
+}
+
+/// Discards all progress.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl Progress for NoProgress {
+    fn start(&self, _phase: Phase, _total: Option<u64>) {}
+    fn advance(&self, _amount: u64) {}
+    fn finish(&self) {}
+}
+
+/// Draws a progress bar per phase on the terminal, with throughput and an ETA when the
+/// phase's total is known and an elapsed-time spinner when it is not.
+///
+/// Clones draw to the same bar, so another thread can stop it.
+#[derive(Debug, Default, Clone)]
+pub struct TerminalProgress {
+    bar: Arc<Mutex<Option<ProgressBar>>>,
+}
+
+impl TerminalProgress {
+    /// Stop drawing the current bar and its spinner, leaving it on screen as it stands.
+    pub fn abandon(&self) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.abandon();
+        }
+    }
+}
+
+impl Progress for TerminalProgress {
+    fn start(&self, phase: Phase, total: Option<u64>) {
+        let bar = match total {
+            Some(total) => {
+                let template = if phase.counts_bytes() {
+                    "{prefix:>26} [{bar:40.green}] {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta}) {msg}"
+                } else {
+                    "{prefix:>26} [{bar:40.green}] {human_pos}/{human_len} ({per_sec}, ETA {eta})"
+                };
+                let bar = ProgressBar::new(total);
+                bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template(template)
+                        .unwrap()
+                        .progress_chars("=> "),
+                );
+                bar
+            }
+            None => {
+                let spinner = ProgressBar::new_spinner();
+                spinner.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{prefix:>26} {spinner:.green} {elapsed_precise}")
+                        .unwrap(),
+                );
+                spinner.enable_steady_tick(Duration::from_millis(100));
+                spinner
+            }
+        };
+        bar.set_prefix(phase.to_string());
+        if let Some(previous) = self.bar.lock().unwrap().replace(bar) {
+            previous.finish();
+        }
+    }
+
+    fn advance(&self, amount: u64) {
+        if let Some(bar) = &*self.bar.lock().unwrap() {
+            bar.inc(amount);
+        }
+    }
+
+    fn compressed(&self, bytes: u64) {
+        if let Some(bar) = &*self.bar.lock().unwrap() {
+            bar.set_message(format!("{} compressed", HumanBytes(bytes)));
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.finish();
+        }
+    }
+
+    fn message(&self, text: &str) {
+        match &*self.bar.lock().unwrap() {
+            Some(bar) => bar.println(text),
+            None => println!("{}", text),
+        }
+    }
+}
+
+// Minimum time between two progress events for the same phase
+const JSON_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes newline-delimited JSON events for orchestrators to follow, to stdout by default.
+///
+/// Every event is an object with an `event` name and the `elapsed_ms` since the reporter was
+/// created. Phases produce `phase_begin` and `phase_end` events, with `progress` events at
+/// most once a second in between.
+pub struct JsonProgress {
+    created: Instant,
+    phase: Mutex<Option<PhaseState>>,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+#[derive(Debug)]
+struct PhaseState {
+    phase: Phase,
+    total: Option<u64>,
+    done: u64,
+    compressed: Option<u64>,
+    started: Instant,
+    reported: Instant,
+}
+
+impl Default for JsonProgress {
+    fn default() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl JsonProgress {
+    /// Write the events to `out` instead of stdout.
+    pub fn new(out: impl Write + Send + 'static) -> Self {
+        Self {
+            created: Instant::now(),
+            phase: Mutex::new(None),
+            out: Mutex::new(Box::new(out)),
+        }
+    }
+
+    /// Write one event; `event` must be a JSON object holding at least an `event` name.
+    pub fn emit(&self, mut event: Value) {
+        if let Some(fields) = event.as_object_mut() {
+            fields.insert(
+                "elapsed_ms".to_string(),
+                json!(self.created.elapsed().as_millis() as u64),
+            );
+        }
+        let mut out = self.out.lock().unwrap();
+        // A closed stdout means nobody is listening, which must not abort generation
+        let _ = writeln!(out, "{}", event).and_then(|()| out.flush());
+    }
+
+    fn emit_progress(&self, state: &PhaseState) {
+        self.emit(json!({
+            "event": "progress",
+            "phase": state.phase,
+            "done": state.done,
+            "total": state.total,
+            "compressed_bytes": state.compressed,
+            "eta_ms": eta(state).map(|eta| eta.as_millis() as u64),
+        }));
+    }
+}
+
+// Linear estimate of the time left in a phase
+fn eta(state: &PhaseState) -> Option<Duration> {
+    let total = state.total?;
+    if state.done == 0 || state.done >= total {
+        return None;
+    }
+    let elapsed = state.started.elapsed().as_secs_f64();
+    Some(Duration::from_secs_f64(
+        elapsed * (total - state.done) as f64 / state.done as f64,
+    ))
+}
+
+impl Progress for JsonProgress {
+    fn start(&self, phase: Phase, total: Option<u64>) {
+        let now = Instant::now();
+        *self.phase.lock().unwrap() = Some(PhaseState {
+            phase,
+            total,
+            done: 0,
+            compressed: None,
+            started: now,
+            reported: now,
+        });
+        self.emit(json!({
+            "event": "phase_begin",
+            "phase": phase,
+            "total": total,
+            "unit": if phase.counts_bytes() { "bytes" } else { "generators" },
+        }));
+    }
+
+    fn advance(&self, amount: u64) {
+        let mut guard = self.phase.lock().unwrap();
+        if let Some(state) = guard.as_mut() {
+            state.done += amount;
+            if state.reported.elapsed() >= JSON_PROGRESS_INTERVAL {
+                state.reported = Instant::now();
+                self.emit_progress(state);
+            }
+        }
+    }
+
+    fn compressed(&self, bytes: u64) {
+        if let Some(state) = self.phase.lock().unwrap().as_mut() {
+            state.compressed = Some(bytes);
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(state) = self.phase.lock().unwrap().take() {
+            self.emit(json!({
+                "event": "phase_end",
+                "phase": state.phase,
+                "done": state.done,
+                "compressed_bytes": state.compressed,
+                "duration_ms": state.started.elapsed().as_millis() as u64,
+            }));
+        }
+    }
+
+    fn message(&self, text: &str) {
+        self.emit(json!({ "event": "message", "text": text }));
+    }
+}
+
+// Passes writes through while reporting how many bytes went by
+pub(crate) struct CountingWriter<W, F> {
+    inner: W,
+    written: u64,
+    report: F,
+}
+
+impl<W: Write, F: FnMut(u64, u64)> CountingWriter<W, F> {
+    // `report` receives the size of each write and the running total
+    pub(crate) fn new(inner: W, report: F) -> Self {
+        Self {
+            inner,
+            written: 0,
+            report,
+        }
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(u64, u64)> Write for CountingWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        (self.report)(n as u64, self.written);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Passes reads through while reporting the size of each one
+pub(crate) struct CountingReader<R, F> {
+    inner: R,
+    report: F,
+}
+
+impl<R: Read, F: FnMut(u64)> CountingReader<R, F> {
+    pub(crate) fn new(inner: R, report: F) -> Self {
+        Self { inner, report }
+    }
+}
+
+impl<R: Read, F: FnMut(u64)> Read for CountingReader<R, F> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        (self.report)(n as u64);
+        Ok(n)
+    }
+}
@@ -0,0 +1,192 @@
+use crate::progress::{Phase, Progress};
+use ark_bls12_381::{G1Affine, G2Affine};
+use ark_ff::UniformRand;
+use ark_serialize::{
+    CanonicalDeserialize, CanonicalSerialize, Compress, Read, SerializationError, Valid, Validate,
+    Write,
+};
+use proof_of_sql::proof_primitive::dory::PublicParameters;
+use rand::{CryptoRng, Rng};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter},
+    path::Path,
+};
+
+/// The group elements of the Dory public parameters, with the fields upstream keeps private.
+///
+/// Serializes to exactly the same bytes as `PublicParameters`, so a `public_parameters.bin`
+/// can be read as either type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawParameters {
+    /// `Γ_1`, of length `2^nu`
+    pub gamma_1: Vec<G1Affine>,
+    /// `Γ_2`, of length `2^nu`
+    pub gamma_2: Vec<G2Affine>,
+    pub h_1: G1Affine,
+    pub h_2: G2Affine,
+    /// `Γ_2,fin`
+    pub gamma_2_fin: G2Affine,
+}
+
+// Pairs of generators drawn between progress reports
+pub(crate) const PROGRESS_CHUNK: usize = 1024;
+
+impl RawParameters {
+    /// Draw the parameters for `nu` from `rng`, reporting each generator to `progress`.
+    ///
+    /// Makes exactly the draws of `PublicParameters::rand` in the same order, so the result
+    /// is identical for the same RNG state: `H_1`, `H_2` and `Γ_2,fin`, then each
+    /// `(Γ_1[i], Γ_2[i])` pair.
+    pub fn rand(nu: usize, rng: &mut (impl CryptoRng + Rng), progress: &dyn Progress) -> Self {
+        progress.start(Phase::Generators, Some(generator_count(nu)));
+        let h_1 = G1Affine::rand(rng);
+        let h_2 = G2Affine::rand(rng);
+        let gamma_2_fin = G2Affine::rand(rng);
+        progress.advance(3);
+
+        let (mut gamma_1, mut gamma_2) = (Vec::with_capacity(1 << nu), Vec::with_capacity(1 << nu));
+        while gamma_1.len() < 1 << nu {
+            let chunk = PROGRESS_CHUNK.min((1 << nu) - gamma_1.len());
+            for _ in 0..chunk {
+                gamma_1.push(G1Affine::rand(rng));
+                gamma_2.push(G2Affine::rand(rng));
+            }
+            progress.advance(2 * chunk as u64);
+        }
+        progress.finish();
+        Self {
+            gamma_1,
+            gamma_2,
+            h_1,
+            h_2,
+            gamma_2_fin,
+        }
+    }
+
+    /// The `nu` these parameters support.
+    pub fn nu(&self) -> usize {
+        self.gamma_1.len().trailing_zeros() as usize
+    }
+
+    /// The parameters for a smaller `nu`, or `None` if `nu` is larger than these support.
+    ///
+    /// Seeded generation samples `H_1`, `H_2` and `Γ_2,fin` first and then the `Γ` pairs in
+    /// order, and hash-to-curve derives each generator from its index, so in both cases this
+    /// equals a fresh generation at `nu`.
+    pub fn truncate(&self, nu: usize) -> Option<Self> {
+        if nu > self.nu() {
+            return None;
+        }
+        Some(Self {
+            gamma_1: self.gamma_1[..1 << nu].to_vec(),
+            gamma_2: self.gamma_2[..1 << nu].to_vec(),
+            h_1: self.h_1,
+            h_2: self.h_2,
+            gamma_2_fin: self.gamma_2_fin,
+        })
+    }
+
+    /// Copy the elements out of a `PublicParameters`.
+    pub fn from_public_parameters(public_parameters: &PublicParameters) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        public_parameters
+            .serialize_with_mode(&mut bytes, Compress::No)
+            .map_err(invalid_data)?;
+        Self::deserialize_with_mode(&bytes[..], Compress::No, Validate::No).map_err(invalid_data)
+    }
+
+    /// Build the `PublicParameters` holding these elements.
+    pub fn to_public_parameters(&self) -> io::Result<PublicParameters> {
+        let mut bytes = Vec::new();
+        self.serialize_with_mode(&mut bytes, Compress::No)
+            .map_err(invalid_data)?;
+        PublicParameters::deserialize_with_mode(&bytes[..], Compress::No, Validate::No)
+            .map_err(invalid_data)
+    }
+
+    /// Write in the `public_parameters.bin` format.
+    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.serialize_with_mode(&mut writer, Compress::No)
+            .map_err(invalid_data)?;
+        writer.flush()
+    }
+
+    /// Read from the `public_parameters.bin` format, checking every element.
+    pub fn load_from_file(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        Self::deserialize_with_mode(reader, Compress::No, Validate::Yes).map_err(invalid_data)
+    }
+}
+
+/// Number of group elements in the parameters for `nu`.
+pub fn generator_count(nu: usize) -> u64 {
+    2 * (1u64 << nu) + 3
+}
+
+fn invalid_data(e: SerializationError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+// Same layout as the `PublicParameters` serialization in proof-of-sql
+impl CanonicalSerialize for RawParameters {
+    fn serialize_with_mode<W: Write>(
+        &self,
+        mut writer: W,
+        compress: Compress,
+    ) -> Result<(), SerializationError> {
+        (self.nu() as u64).serialize_with_mode(&mut writer, compress)?;
+        self.gamma_1
+            .iter()
+            .try_for_each(|g1| g1.serialize_with_mode(&mut writer, compress))?;
+        self.gamma_2
+            .iter()
+            .try_for_each(|g2| g2.serialize_with_mode(&mut writer, compress))?;
+        self.h_1.serialize_with_mode(&mut writer, compress)?;
+        self.h_2.serialize_with_mode(&mut writer, compress)?;
+        self.gamma_2_fin.serialize_with_mode(&mut writer, compress)
+    }
+
+    fn serialized_size(&self, compress: Compress) -> usize {
+        8 + self.gamma_1.len() * self.h_1.serialized_size(compress)
+            + self.gamma_2.len() * self.h_2.serialized_size(compress)
+            + self.h_1.serialized_size(compress)
+            + 2 * self.h_2.serialized_size(compress)
+    }
+}
+
+impl CanonicalDeserialize for RawParameters {
+    fn deserialize_with_mode<R: Read>(
+        mut reader: R,
+        compress: Compress,
+        validate: Validate,
+    ) -> Result<Self, SerializationError> {
+        let nu: usize = u64::deserialize_with_mode(&mut reader, compress, validate)?
+            .try_into()
+            .map_err(|_| SerializationError::InvalidData)?;
+        let gamma_1 = (0..1usize << nu)
+            .map(|_| G1Affine::deserialize_with_mode(&mut reader, compress, validate))
+            .collect::<Result<_, _>>()?;
+        let gamma_2 = (0..1usize << nu)
+            .map(|_| G2Affine::deserialize_with_mode(&mut reader, compress, validate))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            gamma_1,
+            gamma_2,
+            h_1: G1Affine::deserialize_with_mode(&mut reader, compress, validate)?,
+            h_2: G2Affine::deserialize_with_mode(&mut reader, compress, validate)?,
+            gamma_2_fin: G2Affine::deserialize_with_mode(&mut reader, compress, validate)?,
+        })
+    }
+}
+
+impl Valid for RawParameters {
+    fn check(&self) -> Result<(), SerializationError> {
+        self.gamma_1.iter().try_for_each(Valid::check)?;
+        self.gamma_2.iter().try_for_each(Valid::check)?;
+        self.h_1.check()?;
+        self.h_2.check()?;
+        self.gamma_2_fin.check()
+    }
+}
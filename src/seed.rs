@@ -0,0 +1,118 @@
+use crate::hex;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::{fmt, fs, path::PathBuf};
+
+/// Number of bytes in a ChaCha20 seed.
+pub const SEED_LEN: usize = 32;
+
+/// The seed string used by the canonical SxT parameters.
+pub const DEFAULT_SEED: &str = "SpaceAndTime";
+
+// Where a seed came from, so it can be echoed back to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedSource {
+    Default,
+    String(String),
+    Hex,
+    File(PathBuf),
+    /// The final state of a ceremony, identified by the SHA-256 of its transcript
+    Ceremony(String),
+}
+
+impl fmt::Display for SeedSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedSource::Default => write!(f, "default string {:?}", DEFAULT_SEED),
+            SeedSource::String(s) => write!(f, "string {:?}", s),
+            SeedSource::Hex => write!(f, "hex"),
+            SeedSource::File(path) => write!(f, "file {}", path.display()),
+            SeedSource::Ceremony(transcript) => write!(f, "ceremony transcript {}", transcript),
+        }
+    }
+}
+
+/// A resolved 32-byte RNG seed together with how it was derived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seed {
+    pub bytes: [u8; SEED_LEN],
+    pub source: SeedSource,
+}
+
+impl Default for Seed {
+    fn default() -> Self {
+        Self {
+            bytes: pad_str(DEFAULT_SEED).expect("default seed fits in 32 bytes"),
+            source: SeedSource::Default,
+        }
+    }
+}
+
+impl Seed {
+    /// Seed from a UTF-8 string, zero-padded to 32 bytes.
+    pub fn from_string(s: &str) -> Result<Self, String> {
+        Ok(Self {
+            bytes: pad_str(s)?,
+            source: SeedSource::String(s.to_string()),
+        })
+    }
+
+    /// Seed from a 64-character hex string.
+    pub fn from_hex(s: &str) -> Result<Self, String> {
+        Ok(Self {
+            bytes: parse_hex_seed(s)?,
+            source: SeedSource::Hex,
+        })
+    }
+
+    /// Seed from a file containing exactly 32 raw bytes.
+    pub fn from_file(path: PathBuf) -> Result<Self, String> {
+        let contents = fs::read(&path)
+            .map_err(|e| format!("failed to read seed file {}: {}", path.display(), e))?;
+        let bytes = contents.as_slice().try_into().map_err(|_| {
+            format!(
+                "seed file {} must contain exactly {} bytes, found {}",
+                path.display(),
+                SEED_LEN,
+                contents.len()
+            )
+        })?;
+        Ok(Self {
+            bytes,
+            source: SeedSource::File(path),
+        })
+    }
+
+    /// Lowercase hex encoding of the seed bytes.
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.bytes)
+    }
+
+    /// A ChaCha20 RNG seeded with these bytes.
+    pub fn rng(&self) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.bytes)
+    }
+}
+
+// Zero-pad a string to the seed length, rejecting strings that would be truncated
+fn pad_str(s: &str) -> Result<[u8; SEED_LEN], String> {
+    if s.len() > SEED_LEN {
+        return Err(format!(
+            "seed string is {} bytes, at most {} are allowed",
+            s.len(),
+            SEED_LEN
+        ));
+    }
+    let mut bytes = [0u8; SEED_LEN];
+    bytes[..s.len()].copy_from_slice(s.as_bytes());
+    Ok(bytes)
+}
+
+// Parse a 64-character hex string into seed bytes
+fn parse_hex_seed(s: &str) -> Result<[u8; SEED_LEN], String> {
+    let bytes = hex::decode(s).map_err(|e| format!("invalid hex seed: {}", e))?;
+    let len = bytes.len();
+    bytes
+        .try_into()
+        .map_err(|_| format!("hex seed must be {} bytes, found {}", SEED_LEN, len))
+}
@@ -0,0 +1,139 @@
+use ark_bls12_381::{g1, Bls12_381, G1Affine, G2Affine};
+use ark_ec::pairing::PairingOutput;
+use ark_serialize::{CanonicalSerialize, Compress};
+use blitzar::compute::ElementP2;
+use proof_of_sql::proof_primitive::dory::PublicParameters;
+use std::mem::size_of;
+
+/// Number of generators blitzar groups into one partition of its precomputed table.
+pub const BLITZAR_PARTITION_WIDTH: u64 = 16;
+
+// Size of a `usize` or `Vec` length prefix once serialized by ark-serialize
+const LENGTH_PREFIX: u64 = 8;
+
+// Uncompressed serialized sizes of the curve elements making up the Dory setups
+fn g1_size() -> u64 {
+    G1Affine::default().serialized_size(Compress::No) as u64
+}
+
+fn g2_size() -> u64 {
+    G2Affine::default().serialized_size(Compress::No) as u64
+}
+
+fn gt_size() -> u64 {
+    PairingOutput::<Bls12_381>::default().serialized_size(Compress::No) as u64
+}
+
+/// Exact byte sizes of the artifacts produced for a given `nu`, plus the expected peak memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArtifactSizes {
+    /// `public_parameters.bin`: `max_nu`, `Γ_1` and `Γ_2` of length `2^nu`, then `H_1`, `H_2` and `Γ_2,fin`
+    pub public_parameters: u64,
+    /// `blitzar_handle.bin`: a `2^16`-entry table of G1 points for every 16 generators
+    pub blitzar_handle: u64,
+    /// `verifier_setup.bin`: five `nu + 1` length vectors of GT elements plus the fixed elements
+    pub verifier_setup: u64,
+    /// Expected resident memory at the peak of `ProverSetup::from`
+    pub peak_ram: u64,
+}
+
+impl ArtifactSizes {
+    pub fn for_nu(nu: usize) -> Self {
+        let generators = 1u64 << nu;
+        let (g1, g2, gt) = (g1_size(), g2_size(), gt_size());
+
+        let public_parameters = LENGTH_PREFIX + generators * (g1 + g2) + g1 + 2 * g2;
+
+        // blitzar pads the generators to a whole partition and stores each table entry in affine form
+        let partitions = generators.div_ceil(BLITZAR_PARTITION_WIDTH);
+        let blitzar_handle = partitions * (1 << BLITZAR_PARTITION_WIDTH) * g1;
+
+        let gt_vector = LENGTH_PREFIX + (nu as u64 + 1) * gt;
+        let verifier_setup = 5 * gt_vector + 2 * g1 + 3 * g2 + gt + LENGTH_PREFIX;
+
+        // While the handle is built, the in-memory parameters, the projective copy of `Γ_1`
+        // handed to blitzar, and the precomputed table are all live at once
+        let in_memory_parameters =
+            generators * (size_of::<G1Affine>() + size_of::<G2Affine>()) as u64;
+        let projective_gamma_1 = generators * size_of::<ElementP2<g1::Config>>() as u64;
+        let peak_ram = in_memory_parameters + projective_gamma_1 + blitzar_handle;
+
+        Self {
+            public_parameters,
+            blitzar_handle,
+            verifier_setup,
+            peak_ram,
+        }
+    }
+
+    /// Total size of the prover artifacts, plus the verifier setup if it is requested.
+    pub fn total(&self, with_verifier_setup: bool) -> u64 {
+        let verifier_setup = if with_verifier_setup {
+            self.verifier_setup
+        } else {
+            0
+        };
+        self.public_parameters + self.blitzar_handle + verifier_setup
+    }
+}
+
+/// Largest `nu` accepted anywhere: its public parameters alone take over 100 TB, so anything
+/// above cannot be a real parameter set, and `1 << nu` stays far from overflowing.
+pub const MAX_NU: usize = 40;
+
+/// The `nu` a serialized `PublicParameters` starts with, read from its first eight bytes and
+/// rejected if it exceeds [`MAX_NU`].
+pub fn parse_nu_header(head: &[u8]) -> Result<usize, String> {
+    let header: [u8; 8] = head
+        .get(..8)
+        .and_then(|header| header.try_into().ok())
+        .ok_or("too short to hold nu")?;
+    let nu = u64::from_le_bytes(header);
+    if nu > MAX_NU as u64 {
+        return Err(format!(
+            "starts with nu = {}, which is not a real parameter set",
+            nu
+        ));
+    }
+    Ok(nu as usize)
+}
+
+/// The `nu` a set of public parameters was generated for, recovered from its serialized size.
+pub fn nu_of(public_parameters: &PublicParameters) -> usize {
+    let fixed = LENGTH_PREFIX + g1_size() + 2 * g2_size();
+    let generators =
+        (public_parameters.serialized_size(Compress::No) as u64 - fixed) / (g1_size() + g2_size());
+    generators.trailing_zeros() as usize
+}
+
+/// The number of partitions in a blitzar handle of `size` bytes, or `None` if the size is not
+/// a whole number of partitions.
+pub fn blitzar_partitions(size: u64) -> Option<u64> {
+    let partition_size = blitzar_partition_size();
+    size.is_multiple_of(partition_size)
+        .then_some(size / partition_size)
+}
+
+/// Bytes in one partition of a blitzar handle: a G1 point for every subset of its generators.
+pub fn blitzar_partition_size() -> u64 {
+    (1 << BLITZAR_PARTITION_WIDTH) * g1_size()
+}
+
+/// Size of an uncompressed tar archive holding files of the given sizes.
+///
+/// Each member takes a 512-byte header plus its contents padded to 512 bytes, and the archive
+/// ends with two zero blocks. Group elements barely compress, so this is also a close upper
+/// bound for the gzipped archive.
+pub fn tar_size(member_sizes: &[u64]) -> u64 {
+    const BLOCK: u64 = 512;
+    member_sizes
+        .iter()
+        .map(|size| BLOCK + size.div_ceil(BLOCK) * BLOCK)
+        .sum::<u64>()
+        + 2 * BLOCK
+}
+
+/// Format a byte count in MB with two decimals.
+pub fn format_mb(bytes: u64) -> String {
+    format!("{:.2} MB", bytes as f64 / 1_000_000.0)
+}
@@ -0,0 +1,72 @@
+use crate::interrupt;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Distinguishes temp dirs created by the same process within the same clock tick
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A uniquely named directory that is removed, with its contents, when dropped or when the
+/// process is interrupted.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a new directory named `<prefix>-<pid>-<nanos>-<n>` inside `parent`.
+    pub fn new_in(parent: &Path, prefix: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = parent.join(format!("{}-{}-{}-{}", prefix, process::id(), nanos, n));
+        // Registered first, so an interrupt can never leave the directory behind
+        interrupt::register(&path);
+        if let Err(e) = fs::create_dir_all(&path) {
+            interrupt::unregister(&path);
+            return Err(e);
+        }
+        Ok(Self { path })
+    }
+
+    /// Create a new directory inside the system temp directory.
+    pub fn new(prefix: &str) -> io::Result<Self> {
+        Self::new_in(&std::env::temp_dir(), prefix)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Stop managing the directory, leaving it and its contents on disk.
+    pub fn keep(self) -> PathBuf {
+        let path = self.path.clone();
+        interrupt::unregister(&path);
+        std::mem::forget(self);
+        path
+    }
+
+    /// Remove the directory now, reporting any failure instead of ignoring it on drop.
+    pub fn remove(self) -> io::Result<()> {
+        let result = fs::remove_dir_all(&self.path);
+        self.keep();
+        result
+    }
+
+    /// Path of `name` inside this directory.
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+        interrupt::unregister(&self.path);
+    }
+}
@@ -5,6 +5,50 @@ mod tests {
     use std::{fs::File, io::BufReader, path::Path};
     use tar::Archive;
 
+    use crate::{
+        archive::{
+            archive_name_for_nu, first_difference, open_archive, unpack_archive, ArchiveFormat,
+            ArchiveWriter, CompressionOptions, BLITZAR_HANDLE_FILE, PUBLIC_PARAMETERS_FILE,
+            VERIFIER_SETUP_FILE,
+        },
+        atomic::save_atomically,
+        bench::bench_compression,
+        ceremony::{self, Transcript, TRANSCRIPT_FILE},
+        check_archive_names,
+        checkpoint::{WorkDir, CHECKPOINT_FILE},
+        diff::{diff_bundles, ElementDifference, Relation},
+        extend::extend,
+        generate, generate_hash_to_curve,
+        gzip::{ParallelGzEncoder, BLOCK_SIZE},
+        hash_to_curve::{GeneratorHasher, DEFAULT_DST},
+        index::{BundleIndex, INDEX_FILE},
+        inspect::inspect_archive,
+        interrupt::PartialOutputs,
+        load_bundle, load_bundle_parts, load_public_parameters,
+        manifest::{self, Manifest, ManifestEntry, MANIFEST_FILE},
+        metadata::{self, BundleMetadata},
+        package, package_tiers,
+        preflight::{parse_mem_available, Requirements},
+        progress::{JsonProgress, NoProgress, Phase, Progress},
+        prover_setup,
+        raw_parameters::{generator_count, RawParameters},
+        resized_archive_name,
+        seed::{Seed, SeedSource, DEFAULT_SEED},
+        sizes::{nu_of, ArtifactSizes},
+        temp_dir::TempDir,
+        verify::verify_archive,
+        write_bundle, BundleOptions, TierHandles,
+    };
+    use ark_serialize::{CanonicalDeserialize, CanonicalSerialize, Compress, Validate};
+    use proof_of_sql::proof_primitive::dory::VerifierSetup;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+    use std::{
+        io::{self, Read, Write},
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
     // Helper function to untar and decompress a .tar.gz file into the current directory
     fn untar_gz_file(tar_gz_path: &str) -> std::io::Result<()> {
         let tar_gz_file = File::open(tar_gz_path)?; // Open the .tar.gz file
@@ -39,4 +83,1288 @@ mod tests {
         std::fs::remove_file(public_params_path).expect("Failed to delete public_parameters.bin");
         std::fs::remove_file(blitzar_handle_path).expect("Failed to delete blitzar_handle.bin");
     }
+
+    #[test]
+    fn test_seed_inputs_resolve_to_the_same_bytes() {
+        // The default seed is the legacy zero-padded "SpaceAndTime"
+        let default_seed = Seed::default();
+        assert_eq!(
+            &default_seed.bytes[..DEFAULT_SEED.len()],
+            DEFAULT_SEED.as_bytes()
+        );
+        assert!(default_seed.bytes[DEFAULT_SEED.len()..]
+            .iter()
+            .all(|&b| b == 0));
+
+        // String and hex inputs for the same bytes agree
+        let string_seed = Seed::from_string(DEFAULT_SEED).unwrap();
+        let hex_seed = Seed::from_hex(&default_seed.to_hex()).unwrap();
+        assert_eq!(string_seed.bytes, default_seed.bytes);
+        assert_eq!(hex_seed.bytes, default_seed.bytes);
+        assert_eq!(hex_seed.source, SeedSource::Hex);
+
+        // Malformed inputs are rejected
+        assert!(Seed::from_string(&"x".repeat(33)).is_err());
+        assert!(Seed::from_hex("abcd").is_err());
+        assert!(Seed::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_atomic_save_replaces_the_file_without_leaving_a_temporary() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let path = dir.join("saved.json");
+        std::fs::write(&path, b"old").unwrap();
+        save_atomically(&path, |writer| writer.write_all(b"new")).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+
+        // A failed save keeps the old contents
+        let failed = save_atomically(&path, |writer| {
+            writer.write_all(b"partial")?;
+            Err(io::Error::other("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_registered_partial_outputs_are_removed_on_interrupt() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let (file, partial_dir, finished) = (dir.join("a.bin"), dir.join("b"), dir.join("c.bin"));
+        std::fs::write(&file, b"dory").unwrap();
+        std::fs::create_dir(&partial_dir).unwrap();
+        std::fs::write(partial_dir.join("member.bin"), b"dory").unwrap();
+        std::fs::write(&finished, b"dory").unwrap();
+
+        let partial_outputs = PartialOutputs::new();
+        for path in [
+            &file,
+            &partial_dir,
+            &finished,
+            &dir.join("never-written.bin"),
+        ] {
+            partial_outputs.register(path);
+        }
+        partial_outputs.unregister(&finished);
+        let (_partial_outputs, removed) = partial_outputs.remove_all();
+        assert_eq!(removed, vec![file.clone(), partial_dir.clone()]);
+        assert!(!file.exists() && !partial_dir.exists());
+        assert!(finished.exists());
+    }
+
+    #[test]
+    fn test_first_difference_reports_the_mismatching_offset() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let (a, b, c) = (dir.join("a"), dir.join("b"), dir.join("c"));
+        std::fs::write(&a, [1, 2, 3, 4]).unwrap();
+        std::fs::write(&b, [1, 2, 3, 4]).unwrap();
+        std::fs::write(&c, [1, 2, 9]).unwrap();
+
+        assert_eq!(first_difference(&a, &b).unwrap(), None);
+        assert_eq!(first_difference(&a, &c).unwrap(), Some(2));
+        let missing = first_difference(&a, &a.with_extension("missing")).unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_signed_manifest_detects_tampering() {
+        let dir = TempDir::new("dory-test").unwrap();
+        std::fs::write(dir.join("member.bin"), b"dory").unwrap();
+        std::fs::write(dir.join("signing.key"), [7u8; 32]).unwrap();
+
+        let manifest = Manifest::new(4, &Seed::default(), dir.path(), &["member.bin"]).unwrap();
+        assert_eq!(manifest.members[0].size, 4);
+        manifest.check_members(dir.path()).unwrap();
+
+        // The signature verifies against the matching public key only for the signed bytes
+        let key = manifest::load_signing_key(&dir.join("signing.key")).unwrap();
+        let manifest_json = serde_json::to_vec(&manifest).unwrap();
+        let signature = manifest::sign(&key, &manifest_json);
+        std::fs::write(dir.join("public.key"), manifest::public_key_hex(&key)).unwrap();
+        manifest::verify_signature(&dir.join("public.key"), &manifest_json, &signature).unwrap();
+        assert!(manifest::verify_signature(&dir.join("public.key"), b"{}", &signature).is_err());
+
+        // Modified members no longer match their recorded digest
+        std::fs::write(dir.join("member.bin"), b"DORY").unwrap();
+        assert!(manifest.check_members(dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_manifest_records_the_pinned_dependency_versions() {
+        let manifest = Manifest::from_members(1, &Seed::default(), Vec::new());
+        let cargo_toml = include_str!("../Cargo.toml");
+        for (name, version) in [
+            ("proof-of-sql", &manifest.proof_of_sql_version),
+            ("blitzar", &manifest.blitzar_version),
+        ] {
+            assert_eq!(version.split('.').count(), 3, "{} = {}", name, version);
+            assert!(version.split('.').all(|part| part.parse::<u64>().is_ok()));
+            assert!(cargo_toml.contains(&format!("\"={}\"", version)));
+        }
+    }
+
+    #[test]
+    fn test_artifact_sizes_match_serialized_setups() {
+        for nu in [1, 2, 4] {
+            let public_parameters = PublicParameters::rand(nu, &mut Seed::default().rng());
+            let verifier_setup = VerifierSetup::from(&public_parameters);
+            let sizes = ArtifactSizes::for_nu(nu);
+
+            assert_eq!(
+                sizes.public_parameters,
+                public_parameters.serialized_size(Compress::No) as u64
+            );
+            assert_eq!(
+                sizes.verifier_setup,
+                verifier_setup.serialized_size(Compress::No) as u64
+            );
+            assert_eq!(nu_of(&public_parameters), nu);
+        }
+
+        // Small nu still pays for one full blitzar partition, matching the old 6.3 MB figure
+        assert_eq!(ArtifactSizes::for_nu(2).blitzar_handle, 6_291_456);
+        assert_eq!(ArtifactSizes::for_nu(4).blitzar_handle, 6_291_456);
+        assert_eq!(ArtifactSizes::for_nu(5).blitzar_handle, 2 * 6_291_456);
+    }
+
+    #[test]
+    fn test_mem_available_is_parsed_from_meminfo() {
+        let meminfo =
+            "MemTotal:       16303428 kB\nMemFree:         1220304 kB\nMemAvailable:    8151708 kB\n";
+        assert_eq!(parse_mem_available(meminfo), Some(8_151_708 * 1024));
+        assert_eq!(parse_mem_available("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn test_checkpoints_are_checked_against_the_work_dir() {
+        let tiers = [ArtifactSizes::for_nu(2), ArtifactSizes::for_nu(5)];
+        let requirements = Requirements::for_tiers(&tiers, false).checkpointing(&tiers);
+        assert_eq!(
+            requirements.checkpoints,
+            tiers[1].public_parameters + tiers[0].blitzar_handle + tiers[1].blitzar_handle
+        );
+
+        // No disk has room for these checkpoints, wherever the work directory is created
+        let dir = TempDir::new("dory-test").unwrap();
+        let work_dir = dir.join("work");
+        let huge = Requirements {
+            checkpoints: u64::MAX / 2,
+            ..Requirements::new(&tiers[0], false)
+        };
+        assert!(huge.check(dir.path(), None).shortfalls.is_empty());
+        let check = huge.check(dir.path(), Some(&work_dir));
+        assert_eq!(check.shortfalls.len(), 1, "{:?}", check.shortfalls);
+        assert!(requirements
+            .check(dir.path(), Some(&work_dir))
+            .shortfalls
+            .is_empty());
+    }
+
+    // The default bundle options with the default seed, writing into `dir`
+    fn test_options(dir: &Path) -> BundleOptions {
+        BundleOptions {
+            out_dir: dir.to_path_buf(),
+            ..BundleOptions::new(Seed::default())
+        }
+    }
+
+    // Write a bundle of `public_parameters` into `dir` with the test options as adjusted by
+    // `tweak`, returning the archives written
+    fn write_bundle_into(
+        dir: &Path,
+        public_parameters: &PublicParameters,
+        progress: &dyn Progress,
+        tweak: impl FnOnce(&mut BundleOptions),
+    ) -> Vec<PathBuf> {
+        let mut opts = test_options(dir);
+        tweak(&mut opts);
+        let prover_setup = prover_setup(public_parameters, progress).unwrap();
+        write_bundle(prover_setup, public_parameters, &opts, progress)
+            .unwrap()
+            .archives
+    }
+
+    // Write a bundle of the default seed's parameters for `nu` into a new temporary directory,
+    // returning the directory and the prover archive
+    fn write_test_bundle(nu: usize, tweak: impl FnOnce(&mut BundleOptions)) -> (TempDir, PathBuf) {
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(nu, &Seed::default(), &NoProgress).unwrap();
+        let archive =
+            write_bundle_into(dir.path(), &public_parameters, &NoProgress, tweak).remove(0);
+        (dir, archive)
+    }
+
+    #[test]
+    fn test_written_bundle_loads_without_extracting() {
+        let (dir, archive) = write_test_bundle(2, |_| {});
+
+        // Only the archive is left behind in the output directory
+        assert_eq!(archive, dir.join("dory-params.tar.gz"));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let (loaded, _blitzar_handle) = load_bundle_parts(&archive).unwrap();
+        let (mut expected, mut actual) = (Vec::new(), Vec::new());
+        generate(2, &Seed::default(), &NoProgress)
+            .unwrap()
+            .serialize_with_mode(&mut expected, Compress::No)
+            .unwrap();
+        loaded
+            .serialize_with_mode(&mut actual, Compress::No)
+            .unwrap();
+        assert_eq!(actual, expected);
+        // The loaded bundle owns its handle's file and removes it when dropped
+        let bundle = load_bundle(&archive).unwrap();
+        bundle.prover_setup();
+        let handle_path = bundle.blitzar_handle_path();
+        assert!(handle_path.is_file());
+        drop(bundle);
+        assert!(!handle_path.exists());
+    }
+
+    #[test]
+    fn test_verifier_archive_holds_the_derived_verifier_setup() {
+        let (dir, _archive) = write_test_bundle(2, |opts| opts.verifier_setup = true);
+        let unpacked = TempDir::new("dory-test").unwrap();
+        unpack_archive(&dir.join("dory-verifier-params.tar.gz"), unpacked.path()).unwrap();
+
+        let bytes = std::fs::read(unpacked.join(VERIFIER_SETUP_FILE)).unwrap();
+        let verifier_setup =
+            VerifierSetup::deserialize_with_mode(&bytes[..], Compress::No, Validate::Yes).unwrap();
+        let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+        assert_eq!(verifier_setup, VerifierSetup::from(&public_parameters));
+    }
+
+    // Unpack `archive`, let `tamper` change its members, and pack them, in name order, into a
+    // new archive next to it named `name`
+    fn repack(archive: &Path, name: &str, tamper: impl FnOnce(&Path)) -> PathBuf {
+        let unpacked = TempDir::new("dory-test").unwrap();
+        unpack_archive(archive, unpacked.path()).unwrap();
+        tamper(unpacked.path());
+        let mut members: Vec<String> = std::fs::read_dir(unpacked.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        members.sort();
+        let repacked = archive.with_file_name(name);
+        let mut writer =
+            ArchiveWriter::create(&repacked, CompressionOptions::default(), &NoProgress).unwrap();
+        for member in &members {
+            writer.append_file(member, &unpacked.join(member)).unwrap();
+        }
+        writer.finish().unwrap();
+        repacked
+    }
+
+    #[test]
+    fn test_verify_rejects_tampered_archives() {
+        let dir = TempDir::new("dory-test").unwrap();
+        std::fs::write(dir.join("signing.key"), [7u8; 32]).unwrap();
+        std::fs::write(dir.join("other.key"), [8u8; 32]).unwrap();
+        let key = manifest::load_signing_key(&dir.join("signing.key")).unwrap();
+        let other_key = manifest::load_signing_key(&dir.join("other.key")).unwrap();
+        std::fs::write(dir.join("public.key"), manifest::public_key_hex(&key)).unwrap();
+        std::fs::write(dir.join("other.pub"), manifest::public_key_hex(&other_key)).unwrap();
+        let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+        let archive = write_bundle_into(dir.path(), &public_parameters, &NoProgress, |opts| {
+            opts.signing_key = Some(manifest::load_signing_key(&dir.join("signing.key")).unwrap());
+        })
+        .remove(0);
+        let verify = |archive: &Path, public_key: Option<&Path>| {
+            verify_archive(archive, false, None, public_key, &NoProgress)
+        };
+        verify(&archive, None).unwrap();
+        verify(&archive, Some(&dir.join("public.key"))).unwrap();
+        // Repacking alone changes nothing the verifier checks
+        let repacked = repack(&archive, "repacked.tar.gz", |_| {});
+        verify(&repacked, Some(&dir.join("public.key"))).unwrap();
+
+        // A member whose bytes no longer match its digest
+        let tampered = repack(&archive, "tampered.tar.gz", |members| {
+            let path = members.join(BLITZAR_HANDLE_FILE);
+            let mut bytes = std::fs::read(&path).unwrap();
+            bytes[100] ^= 1;
+            std::fs::write(&path, bytes).unwrap();
+        });
+        assert!(verify(&tampered, None).is_err());
+
+        // A listed member that is missing, and a member that is not listed
+        let missing = repack(&archive, "missing.tar.gz", |members| {
+            std::fs::remove_file(members.join(metadata::METADATA_FILE)).unwrap();
+        });
+        assert!(verify(&missing, None).is_err());
+        let extra = repack(&archive, "extra.tar.gz", |members| {
+            std::fs::write(members.join("extra.bin"), b"dory").unwrap();
+        });
+        assert!(verify(&extra, None).is_err());
+
+        // A signature from another key, or none at all, when a public key is given
+        assert!(verify(&archive, Some(&dir.join("other.pub"))).is_err());
+        let unsigned = repack(&archive, "unsigned.tar.gz", |members| {
+            std::fs::remove_file(members.join(manifest::SIGNATURE_FILE)).unwrap();
+        });
+        verify(&unsigned, None).unwrap();
+        assert!(verify(&unsigned, Some(&dir.join("public.key"))).is_err());
+        let forged = repack(&archive, "forged.tar.gz", |members| {
+            let manifest_json = std::fs::read(members.join(MANIFEST_FILE)).unwrap();
+            std::fs::write(
+                members.join(manifest::SIGNATURE_FILE),
+                manifest::sign(&other_key, &manifest_json),
+            )
+            .unwrap();
+        });
+        assert!(verify(&forged, Some(&dir.join("public.key"))).is_err());
+
+        // Parameters from another seed, recorded as coming from the default seed
+        let other = generate(2, &Seed::from_string("other").unwrap(), &NoProgress).unwrap();
+        let mislabelled = write_bundle_into(dir.path(), &other, &NoProgress, |opts| {
+            opts.archive_name = "mislabelled.tar.gz".to_string();
+        })
+        .remove(0);
+        verify(&mislabelled, None).unwrap();
+        assert!(verify_archive(&mislabelled, true, None, None, &NoProgress).is_err());
+        verify_archive(&archive, true, None, None, &NoProgress).unwrap();
+    }
+
+    #[test]
+    fn test_ceremony_contributions_verify_and_detect_tampering() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let seed = Seed::default();
+        ceremony::init(dir.path(), 2, &seed).unwrap();
+        ceremony::contribute(dir.path(), "alice").unwrap();
+        ceremony::contribute(dir.path(), "bob").unwrap();
+
+        let (public_parameters, transcript) = ceremony::finalize(dir.path()).unwrap();
+        assert_eq!(transcript.contributions.len(), 2);
+        assert_eq!(nu_of(&public_parameters), 2);
+        let initial =
+            RawParameters::from_public_parameters(&generate(2, &seed, &NoProgress).unwrap())
+                .unwrap();
+        let last = RawParameters::from_public_parameters(&public_parameters).unwrap();
+        assert_eq!(last.nu(), 2);
+        assert!(last
+            .gamma_1
+            .iter()
+            .zip(&initial.gamma_1)
+            .all(|(a, b)| a != b));
+
+        // Replacing bob's state with alice's, even with a matching digest, breaks his proof
+        let mut tampered: Transcript = Transcript::load(dir.path()).unwrap();
+        std::fs::copy(
+            dir.join(&tampered.contributions[0].state.file),
+            dir.join(&tampered.contributions[1].state.file),
+        )
+        .unwrap();
+        tampered.contributions[1].state.sha256 = tampered.contributions[0].state.sha256.clone();
+        std::fs::write(
+            dir.join(TRANSCRIPT_FILE),
+            serde_json::to_vec(&tampered).unwrap(),
+        )
+        .unwrap();
+        let error = ceremony::verify(dir.path()).unwrap_err();
+        assert!(error.contains("contribution 2 by bob"), "{}", error);
+
+        // An initial state that the recorded seed does not produce is refused
+        let other_dir = TempDir::new("dory-test").unwrap();
+        ceremony::init(other_dir.path(), 2, &seed).unwrap();
+        ceremony::verify(other_dir.path()).unwrap();
+        let mut relabelled = Transcript::load(other_dir.path()).unwrap();
+        relabelled.initial_seed = Seed::from_string("other").unwrap().to_hex();
+        std::fs::write(
+            other_dir.join(TRANSCRIPT_FILE),
+            serde_json::to_vec(&relabelled).unwrap(),
+        )
+        .unwrap();
+        let error = ceremony::verify(other_dir.path()).unwrap_err();
+        assert!(error.contains("initial state"), "{}", error);
+    }
+
+    #[test]
+    fn test_hash_to_curve_generators_can_be_spot_checked() {
+        let hasher = GeneratorHasher::new(DEFAULT_DST).unwrap();
+        let mut parameters = hasher.derive(3, &NoProgress);
+        assert_eq!(parameters.gamma_1[5], hasher.gamma_1(5));
+        assert_eq!(parameters.gamma_2[7], hasher.gamma_2(7));
+        assert_ne!(parameters.gamma_1[0], parameters.gamma_1[1]);
+
+        let public_parameters = generate_hash_to_curve(3, DEFAULT_DST, &NoProgress).unwrap();
+        assert_eq!(nu_of(&public_parameters), 3);
+        assert_eq!(
+            RawParameters::from_public_parameters(&public_parameters).unwrap(),
+            parameters
+        );
+
+        let mut rng = ChaCha20Rng::from_seed([0; 32]);
+        hasher.spot_check(&parameters, 8, &mut rng).unwrap();
+        parameters.gamma_1.swap(0, 1);
+        assert!(hasher.spot_check(&parameters, 64, &mut rng).is_err());
+        assert!(GeneratorHasher::new("OTHER-DST")
+            .unwrap()
+            .spot_check(&hasher.derive(1, &NoProgress), 1, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_hash_to_curve_bundle_records_its_tag_instead_of_a_seed() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate_hash_to_curve(2, DEFAULT_DST, &NoProgress).unwrap();
+        let archives = write_bundle_into(dir.path(), &public_parameters, &NoProgress, |opts| {
+            opts.hash_to_curve_dst = Some(DEFAULT_DST.to_string())
+        });
+
+        let inspection = inspect_archive(&archives[0]).unwrap();
+        let metadata = inspection.metadata.unwrap();
+        assert_eq!(metadata, BundleMetadata::hash_to_curve(2, DEFAULT_DST));
+        assert!(metadata.seed().is_err());
+        assert!(!serde_json::to_string(&metadata).unwrap().contains("seed"));
+        let manifest = inspection.manifest.unwrap();
+        assert_eq!(manifest.seed_fingerprint, None);
+        assert_eq!(manifest.hash_to_curve_dst.as_deref(), Some(DEFAULT_DST));
+    }
+
+    #[test]
+    fn test_smaller_tiers_are_prefixes_of_the_largest() {
+        let seed = Seed::default();
+        let largest =
+            RawParameters::from_public_parameters(&generate(4, &seed, &NoProgress).unwrap())
+                .unwrap();
+        for nu in 0..=4 {
+            let fresh =
+                RawParameters::from_public_parameters(&generate(nu, &seed, &NoProgress).unwrap())
+                    .unwrap();
+            assert_eq!(largest.truncate(nu).unwrap(), fresh, "nu = {}", nu);
+        }
+        assert!(largest.truncate(5).is_none());
+
+        assert_eq!(
+            archive_name_for_nu("dory-params.tar.gz", 16),
+            "dory-params-nu16.tar.gz"
+        );
+        assert_eq!(archive_name_for_nu("params", 8), "params-nu8");
+    }
+
+    #[test]
+    fn test_extended_parameters_match_a_fresh_generation() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let seed = Seed::from_string("extend").unwrap();
+        let public_parameters = generate(2, &seed, &NoProgress).unwrap();
+        let archives = write_bundle_into(dir.path(), &public_parameters, &NoProgress, |opts| {
+            opts.seed = seed.clone()
+        });
+
+        // Extend straight from what the archive recorded
+        let (loaded, metadata) = load_public_parameters(&archives[0]).unwrap();
+        let metadata = metadata.unwrap();
+        let small = RawParameters::from_public_parameters(&loaded).unwrap();
+        let extended = extend(&small, &metadata, 5, &NoProgress).unwrap();
+        let fresh =
+            RawParameters::from_public_parameters(&generate(5, &seed, &NoProgress).unwrap())
+                .unwrap();
+        assert_eq!(extended, fresh);
+        assert!(extend(&small, &metadata, 2, &NoProgress).is_err());
+
+        // A different seed is detected instead of silently producing inconsistent generators
+        let mut wrong_seed = metadata.clone();
+        wrong_seed.seed = Some(Seed::default().to_hex());
+        assert!(extend(&small, &wrong_seed, 3, &NoProgress).is_err());
+
+        let hash_to_curve = metadata::BundleMetadata {
+            hash_to_curve_dst: Some(DEFAULT_DST.to_string()),
+            ..metadata
+        };
+        let derived = GeneratorHasher::new(DEFAULT_DST).unwrap();
+        assert_eq!(
+            extend(
+                &derived.derive(1, &NoProgress),
+                &hash_to_curve,
+                3,
+                &NoProgress
+            )
+            .unwrap(),
+            derived.derive(3, &NoProgress)
+        );
+    }
+
+    #[test]
+    fn test_smaller_tier_handles_are_cut_from_the_largest() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let largest = generate(5, &Seed::default(), &NoProgress).unwrap();
+        let truncate = |nu| {
+            RawParameters::from_public_parameters(&largest)
+                .unwrap()
+                .truncate(nu)
+                .unwrap()
+                .to_public_parameters()
+                .unwrap()
+        };
+        let smaller = truncate(4);
+
+        // Nothing can be cut until the largest handle is kept
+        let mut tiers = TierHandles::new_in(dir.path()).unwrap();
+        assert!(tiers.cut(&smaller, &NoProgress).unwrap().is_none());
+        let handle = prover_setup(&largest, &NoProgress)
+            .unwrap()
+            .blitzar_handle();
+        tiers.keep(5, &handle, &NoProgress).unwrap();
+        assert!(tiers.covers(4) && tiers.covers(5) && !tiers.covers(6));
+
+        // The cut handle matches the one built directly for the smaller tier
+        let cut = tiers.cut(&smaller, &NoProgress).unwrap().unwrap();
+        let cut_dir = TempDir::new("dory-test").unwrap();
+        let cut_archive = write_bundle(cut, &smaller, &test_options(cut_dir.path()), &NoProgress)
+            .unwrap()
+            .archives
+            .remove(0);
+        let built_dir = TempDir::new("dory-test").unwrap();
+        let built_archive =
+            write_bundle_into(built_dir.path(), &smaller, &NoProgress, |_| {}).remove(0);
+        let cut_unpacked = TempDir::new("dory-test").unwrap();
+        let built_unpacked = TempDir::new("dory-test").unwrap();
+        unpack_archive(&cut_archive, cut_unpacked.path()).unwrap();
+        unpack_archive(&built_archive, built_unpacked.path()).unwrap();
+        assert_eq!(
+            first_difference(
+                &cut_unpacked.join(BLITZAR_HANDLE_FILE),
+                &built_unpacked.join(BLITZAR_HANDLE_FILE)
+            )
+            .unwrap(),
+            None
+        );
+
+        // Tiers whose last partition is padded are built instead
+        assert!(tiers.cut(&truncate(2), &NoProgress).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tier_list_writes_named_archives_and_an_index() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(3, &Seed::default(), &NoProgress).unwrap();
+        let opts = BundleOptions {
+            verifier_setup: true,
+            ..test_options(dir.path())
+        };
+
+        let output = package_tiers(public_parameters, &[1, 2, 3], opts, None, &NoProgress).unwrap();
+        let archives: Vec<Vec<PathBuf>> = output
+            .bundles
+            .iter()
+            .map(|bundle| bundle.archives.clone())
+            .collect();
+        assert_eq!(
+            archives,
+            [1, 2, 3]
+                .map(|nu| vec![
+                    dir.join(&format!("dory-params-nu{}.tar.gz", nu)),
+                    dir.join(&format!("dory-verifier-params-nu{}.tar.gz", nu)),
+                ])
+                .to_vec()
+        );
+
+        // The index lists every archive in increasing order of nu, with its digest
+        let index_path = output.index.unwrap();
+        assert_eq!(index_path, dir.join(INDEX_FILE));
+        let index = BundleIndex::load_from_file(&index_path).unwrap();
+        assert_eq!(index.bundles.len(), 3);
+        for ((entry, nu), archives) in index.bundles.iter().zip(1..).zip(&archives) {
+            assert_eq!(entry.nu, nu);
+            let expected: Vec<ManifestEntry> = archives
+                .iter()
+                .map(|archive| ManifestEntry::for_file(archive).unwrap())
+                .collect();
+            assert_eq!(entry.archives, expected);
+        }
+        for (bundle, entry) in output.bundles.iter().zip(&index.bundles) {
+            assert_eq!(bundle.digests, entry.archives);
+        }
+
+        // A single tier keeps its names and writes no index
+        let single_dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+        let output = package_tiers(
+            public_parameters,
+            &[2],
+            test_options(single_dir.path()),
+            None,
+            &NoProgress,
+        )
+        .unwrap();
+        assert_eq!(
+            output.bundles[0].archives,
+            vec![single_dir.join("dory-params.tar.gz")]
+        );
+        assert!(output.index.is_none());
+        assert!(!single_dir.join(INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn test_truncated_bundle_records_its_parent() {
+        let (dir, parent) = write_test_bundle(3, |_| {});
+
+        let (loaded, metadata) = load_public_parameters(&parent).unwrap();
+        let truncated = RawParameters::from_public_parameters(&loaded)
+            .unwrap()
+            .truncate(1)
+            .unwrap()
+            .to_public_parameters()
+            .unwrap();
+        let parent_entry = ManifestEntry::for_file(&parent).unwrap();
+        let child = write_bundle_into(dir.path(), &truncated, &NoProgress, |opts| {
+            opts.archive_name = archive_name_for_nu(&opts.archive_name, 1);
+            opts.parent_archive = Some(parent_entry.clone());
+            opts.seed = metadata.unwrap().seed().unwrap();
+        });
+
+        let unpacked = TempDir::new("dory-test").unwrap();
+        unpack_archive(&child[0], unpacked.path()).unwrap();
+        let manifest: Manifest =
+            serde_json::from_slice(&std::fs::read(unpacked.join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(manifest.nu, 1);
+        assert_eq!(manifest.parent_archive, Some(parent_entry));
+        assert_eq!(
+            manifest.seed_fingerprint,
+            Manifest::new(1, &Seed::default(), unpacked.path(), &[])
+                .unwrap()
+                .seed_fingerprint
+        );
+    }
+
+    #[test]
+    fn test_truncated_bundle_keeps_a_requested_archive_name() {
+        let default_name = ArchiveFormat::TarGz.default_archive_name();
+        assert_eq!(
+            resized_archive_name(None, &default_name, 1),
+            "dory-params-nu1.tar.gz"
+        );
+        assert_eq!(
+            resized_archive_name(Some("small.tar.gz"), &default_name, 1),
+            "small.tar.gz"
+        );
+
+        let (dir, parent) = write_test_bundle(3, |_| {});
+        let (loaded, _) = load_public_parameters(&parent).unwrap();
+        let truncated = RawParameters::from_public_parameters(&loaded)
+            .unwrap()
+            .truncate(1)
+            .unwrap()
+            .to_public_parameters()
+            .unwrap();
+        let child = write_bundle_into(dir.path(), &truncated, &NoProgress, |opts| {
+            opts.archive_name = resized_archive_name(Some("small.tar.gz"), &default_name, 1);
+        });
+        assert_eq!(child, vec![dir.join("small.tar.gz")]);
+        assert!(!dir.join("small-nu1.tar.gz").exists());
+    }
+
+    // Records each phase with its total and how far it advanced
+    #[derive(Default)]
+    struct RecordingProgress {
+        phases: Mutex<Vec<(Phase, Option<u64>, u64)>>,
+    }
+
+    impl Progress for RecordingProgress {
+        fn start(&self, phase: Phase, total: Option<u64>) {
+            self.phases.lock().unwrap().push((phase, total, 0));
+        }
+
+        fn advance(&self, amount: u64) {
+            self.phases.lock().unwrap().last_mut().unwrap().2 += amount;
+        }
+
+        fn finish(&self) {}
+    }
+
+    #[test]
+    fn test_progress_covers_every_generator_and_byte() {
+        let seed = Seed::default();
+        let progress = RecordingProgress::default();
+        let public_parameters = generate(5, &seed, &progress).unwrap();
+
+        // Chunked generation makes exactly the draws of the upstream implementation
+        let (mut expected, mut actual) = (Vec::new(), Vec::new());
+        PublicParameters::rand(5, &mut seed.rng())
+            .serialize_with_mode(&mut expected, Compress::No)
+            .unwrap();
+        public_parameters
+            .serialize_with_mode(&mut actual, Compress::No)
+            .unwrap();
+        assert_eq!(actual, expected);
+
+        let dir = TempDir::new("dory-test").unwrap();
+        write_bundle_into(dir.path(), &public_parameters, &progress, |_| {});
+
+        let phases = progress.phases.into_inner().unwrap();
+        let names: Vec<Phase> = phases.iter().map(|(phase, _, _)| *phase).collect();
+        assert_eq!(
+            names,
+            vec![
+                Phase::Generators,
+                Phase::ProverSetup,
+                Phase::PublicParameters,
+                Phase::BlitzarHandle,
+                Phase::Archive
+            ]
+        );
+        assert_eq!(phases[0].1, Some(generator_count(5)));
+        for (phase, total, advanced) in phases {
+            if let Some(total) = total {
+                assert_eq!(advanced, total, "{}", phase);
+            }
+        }
+    }
+
+    // A writer whose contents stay readable after it is handed off
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_progress_writes_one_event_per_line() {
+        let buffer = SharedBuffer::default();
+        let progress = JsonProgress::new(buffer.clone());
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(4, &Seed::default(), &progress).unwrap();
+        let archives = write_bundle_into(dir.path(), &public_parameters, &progress, |_| {});
+        verify_archive(&archives[0], false, None, None, &progress).unwrap();
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(events.iter().all(|event| event["elapsed_ms"].is_u64()));
+
+        // Every phase begins and ends in order, and generation reports every generator
+        let phases: Vec<(&str, &str)> = events
+            .iter()
+            .filter(|event| event["event"] != "progress" && event["event"] != "message")
+            .map(|event| {
+                (
+                    event["event"].as_str().unwrap(),
+                    event["phase"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        let mut expected = Vec::new();
+        for phase in [
+            "generators",
+            "prover_setup",
+            "public_parameters",
+            "blitzar_handle",
+            "archive",
+        ] {
+            expected.push(("phase_begin", phase));
+            expected.push(("phase_end", phase));
+        }
+        assert_eq!(phases, expected);
+        assert_eq!(events[1]["done"], generator_count(4));
+        assert!(events[1]["duration_ms"].is_u64());
+
+        // Status lines from verification arrive as message events
+        assert!(events.iter().any(|event| event["event"] == "message"
+            && event["text"]
+                .as_str()
+                .unwrap()
+                .contains("manifest members match")));
+    }
+
+    #[test]
+    fn test_parallel_gzip_is_readable_and_independent_of_thread_count() {
+        // Several blocks with a partial last one, mixing compressible and random bytes
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let data: Vec<u8> = (0..3 * BLOCK_SIZE + 12345)
+            .map(|i| {
+                if i % 3 == 0 {
+                    rng.gen()
+                } else {
+                    (i % 251) as u8
+                }
+            })
+            .collect();
+        let compress = |data: &[u8], threads| {
+            let mut encoder = ParallelGzEncoder::new(Vec::new(), 6, threads).unwrap();
+            // Odd-sized writes must not change the output either
+            for chunk in data.chunks(100_003) {
+                encoder.write_all(chunk).unwrap();
+            }
+            encoder.finish().unwrap()
+        };
+
+        let single = compress(&data, 1);
+        assert_eq!(compress(&data, 4), single);
+        assert!(single.len() < data.len());
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&single[..])
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, data);
+
+        // Empty input and input ending on a batch boundary still form a complete stream
+        for data in [Vec::new(), vec![1u8; 2 * BLOCK_SIZE]] {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(&compress(&data, 2)[..])
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    #[test]
+    fn test_every_archive_format_is_detected_and_loads() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(3, &Seed::default(), &NoProgress).unwrap();
+        for format in ArchiveFormat::ALL {
+            let archives = write_bundle_into(dir.path(), &public_parameters, &NoProgress, |opts| {
+                opts.archive_name = format!("dory-params{}", format.extension());
+                opts.compression = CompressionOptions {
+                    threads: 2,
+                    ..CompressionOptions::new(format)
+                };
+            });
+            let archive = &archives[0];
+            assert_eq!(ArchiveFormat::detect(archive).unwrap(), format);
+            assert_eq!(format.extension()[1..].parse::<ArchiveFormat>(), Ok(format));
+            let (loaded, _blitzar_handle) = load_bundle_parts(archive).unwrap();
+            assert_eq!(
+                RawParameters::from_public_parameters(&loaded).unwrap(),
+                RawParameters::from_public_parameters(&public_parameters).unwrap()
+            );
+
+            // Recompressing in every format reproduces the same tar stream
+            let benchmarks = bench_compression(archive, 2, &NoProgress).unwrap();
+            assert_eq!(benchmarks.len(), ArchiveFormat::ALL.len());
+            assert!(benchmarks
+                .iter()
+                .all(|benchmark| benchmark.uncompressed_size == benchmarks[0].uncompressed_size));
+        }
+
+        // Anything else is rejected rather than guessed
+        let garbage = dir.join("garbage.tar.gz");
+        std::fs::write(&garbage, [0u8; 1024]).unwrap();
+        assert!(ArchiveFormat::detect(&garbage).is_err());
+    }
+
+    #[test]
+    fn test_compression_levels_are_checked_against_the_format() {
+        for format in ArchiveFormat::ALL {
+            let options = |level| CompressionOptions {
+                level: Some(level),
+                ..CompressionOptions::new(format)
+            };
+            assert!(options(format.max_level()).check_level().is_ok());
+            let too_high = options(format.max_level() + 1).check_level().unwrap_err();
+            assert_eq!(too_high.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn test_archive_names_must_be_distinct_plain_file_names() {
+        let verifier = "dory-verifier-params.tar.gz";
+        assert!(check_archive_names("dory-params.tar.gz", verifier, true).is_ok());
+        for name in [
+            "",
+            ".",
+            "..",
+            "../dory-params.tar.gz",
+            "out/dory-params.tar.gz",
+            "/tmp/x",
+        ] {
+            assert!(
+                check_archive_names(name, verifier, false).is_err(),
+                "{:?}",
+                name
+            );
+        }
+        assert!(check_archive_names("dory-params.tar.gz", "../v.tar.gz", true).is_err());
+        // The verifier name only matters when a verifier archive is written
+        assert!(check_archive_names("dory-params.tar.gz", "../v.tar.gz", false).is_ok());
+        assert!(check_archive_names(verifier, verifier, true).is_err());
+        assert!(check_archive_names(verifier, verifier, false).is_ok());
+
+        // A bundle is refused before anything is written
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+        let opts = BundleOptions {
+            archive_name: "../escaped.tar.gz".to_string(),
+            ..test_options(&dir.join("out"))
+        };
+        let prover_setup = prover_setup(&public_parameters, &NoProgress).unwrap();
+        assert!(write_bundle(prover_setup, &public_parameters, &opts, &NoProgress).is_err());
+        assert!(!dir.join("escaped.tar.gz").exists());
+        assert!(!dir.join("out").exists());
+    }
+
+    #[test]
+    fn test_bundle_is_streamed_without_leaving_files_behind() {
+        let (dir, _archive) = write_test_bundle(2, |opts| opts.verifier_setup = true);
+
+        // Only the two archives remain, with no staging directory or .bin files
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["dory-params.tar.gz", "dory-verifier-params.tar.gz"]
+        );
+
+        // The manifest written alongside the streamed members matches them
+        for name in names {
+            let unpacked = TempDir::new("dory-test").unwrap();
+            unpack_archive(&dir.join(&name), unpacked.path()).unwrap();
+            let manifest: Manifest =
+                serde_json::from_slice(&std::fs::read(unpacked.join(MANIFEST_FILE)).unwrap())
+                    .unwrap();
+            manifest.check_members(unpacked.path()).unwrap();
+            assert!(manifest
+                .members
+                .iter()
+                .any(|member| member.name == metadata::METADATA_FILE));
+        }
+    }
+
+    #[test]
+    fn test_kept_intermediates_match_the_archived_members() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+        let opts = BundleOptions {
+            verifier_setup: true,
+            keep_intermediates: true,
+            ..test_options(dir.path())
+        };
+        let output = write_bundle(
+            prover_setup(&public_parameters, &NoProgress).unwrap(),
+            &public_parameters,
+            &opts,
+            &NoProgress,
+        )
+        .unwrap();
+        assert_eq!(
+            output.intermediates,
+            vec![dir.join("dory-params"), dir.join("dory-verifier-params")]
+        );
+
+        // Each directory holds exactly the members of the archive next to it
+        let names = |dir: &Path| {
+            let mut names: Vec<String> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        for (archive, kept) in output.archives.iter().zip(&output.intermediates) {
+            let unpacked = TempDir::new("dory-test").unwrap();
+            unpack_archive(archive, unpacked.path()).unwrap();
+            assert_eq!(names(kept), names(unpacked.path()));
+            for name in names(kept) {
+                assert_eq!(
+                    first_difference(&kept.join(&name), &unpacked.join(&name)).unwrap(),
+                    None,
+                    "{}",
+                    name
+                );
+            }
+        }
+
+        // The kept parameters load on their own
+        let loaded =
+            PublicParameters::load_from_file(&dir.join("dory-params").join(PUBLIC_PARAMETERS_FILE))
+                .unwrap();
+        assert_eq!(
+            RawParameters::from_public_parameters(&loaded).unwrap(),
+            RawParameters::from_public_parameters(&public_parameters).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_resumed_run_reuses_checkpoints_after_checking_their_digests() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let work_dir_path = dir.join("work");
+        let seed = Seed::default();
+        let metadata = BundleMetadata::new(2, &seed);
+        let public_parameters = generate(2, &seed, &NoProgress).unwrap();
+
+        // A run that stops after building the prover setup
+        let mut work_dir = WorkDir::create(&work_dir_path, metadata.clone()).unwrap();
+        work_dir
+            .save_public_parameters(&public_parameters, &NoProgress)
+            .unwrap();
+        let blitzar_handle = prover_setup(&public_parameters, &NoProgress)
+            .unwrap()
+            .blitzar_handle();
+        work_dir
+            .save_blitzar_handle(2, &blitzar_handle, &NoProgress)
+            .unwrap();
+        drop(work_dir);
+        assert!(WorkDir::create(&work_dir_path, metadata.clone()).is_err());
+        let other_run = BundleMetadata::new(2, &Seed::from_string("other").unwrap());
+        assert!(WorkDir::resume(&work_dir_path, &other_run).is_err());
+
+        // Resuming skips generation and the prover setup
+        let mut work_dir = WorkDir::resume(&work_dir_path, &metadata).unwrap();
+        let resumed = work_dir.load_public_parameters().unwrap().unwrap();
+        let (mut expected, mut actual) = (Vec::new(), Vec::new());
+        public_parameters
+            .serialize_with_mode(&mut expected, Compress::No)
+            .unwrap();
+        resumed
+            .serialize_with_mode(&mut actual, Compress::No)
+            .unwrap();
+        assert_eq!(actual, expected);
+        let resumed_setup = work_dir.load_prover_setup(&resumed).unwrap().unwrap();
+
+        // Written from the resumed setup rather than a fresh one
+        let opts = test_options(dir.path());
+        let output = write_bundle(resumed_setup, &resumed, &opts, &NoProgress).unwrap();
+        load_bundle(&output.archives[0]).unwrap().prover_setup();
+        assert!(!work_dir.archives_finished(2, &output.archives));
+        work_dir.record_archives(2, &output).unwrap();
+        assert!(work_dir.archives_finished(2, &output.archives));
+        assert!(WorkDir::resume(&work_dir_path, &metadata)
+            .unwrap()
+            .archives_finished(2, &output.archives));
+        assert!(work_dir_path.join(CHECKPOINT_FILE).exists());
+
+        // A changed archive is written again, while a changed checkpoint artifact is refused
+        let mut archive = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&output.archives[0])
+            .unwrap();
+        archive.write_all(b"\0").unwrap();
+        assert!(!work_dir.archives_finished(2, &output.archives));
+        let parameters_path = work_dir_path.join(PUBLIC_PARAMETERS_FILE);
+        let mut bytes = std::fs::read(&parameters_path).unwrap();
+        bytes[100] ^= 1;
+        std::fs::write(&parameters_path, bytes).unwrap();
+        assert!(work_dir.load_public_parameters().is_err());
+    }
+
+    #[test]
+    fn test_resuming_a_tier_list_skips_the_tiers_already_packaged() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let work_dir_path = dir.join("work");
+        let seed = Seed::default();
+        let metadata = BundleMetadata::new(3, &seed);
+        let largest = generate(3, &seed, &NoProgress).unwrap();
+        let smaller = RawParameters::from_public_parameters(&largest)
+            .unwrap()
+            .truncate(2)
+            .unwrap()
+            .to_public_parameters()
+            .unwrap();
+        let options_for = |nu| BundleOptions {
+            archive_name: archive_name_for_nu("dory-params.tar.gz", nu),
+            ..test_options(dir.path())
+        };
+
+        // A run over nu = 3 and 2 that stops once the largest tier is packaged
+        let mut work_dir = WorkDir::create(&work_dir_path, metadata.clone()).unwrap();
+        work_dir
+            .save_public_parameters(&largest, &NoProgress)
+            .unwrap();
+        let mut tiers = TierHandles::new_in(dir.path()).unwrap();
+        let first = package(
+            &largest,
+            &options_for(3),
+            Some(&mut work_dir),
+            Some(&mut tiers),
+            &NoProgress,
+        )
+        .unwrap();
+        drop((work_dir, tiers));
+        let modified = std::fs::metadata(&first.archives[0])
+            .unwrap()
+            .modified()
+            .unwrap();
+
+        // Resuming leaves the finished tier alone and cuts the next one from its checkpoint
+        let mut work_dir = WorkDir::resume(&work_dir_path, &metadata).unwrap();
+        let mut tiers = TierHandles::new_in(dir.path()).unwrap();
+        let skipped = package(
+            &largest,
+            &options_for(3),
+            Some(&mut work_dir),
+            Some(&mut tiers),
+            &NoProgress,
+        )
+        .unwrap();
+        assert_eq!(skipped.archives, first.archives);
+        assert_eq!(skipped.digests, first.digests);
+        assert_eq!(
+            std::fs::metadata(&first.archives[0])
+                .unwrap()
+                .modified()
+                .unwrap(),
+            modified
+        );
+        assert!(tiers.covers(2));
+        let second = package(
+            &smaller,
+            &options_for(2),
+            Some(&mut work_dir),
+            Some(&mut tiers),
+            &NoProgress,
+        )
+        .unwrap();
+        assert_eq!(second.archives, vec![dir.join("dory-params-nu2.tar.gz")]);
+        assert!(work_dir.archives_finished(2, &second.archives));
+        let loaded = load_bundle(&second.archives[0]).unwrap();
+        assert_eq!(nu_of(loaded.public_parameters()), 2);
+        loaded.prover_setup();
+    }
+
+    #[test]
+    fn test_inspect_describes_a_bundle_without_loading_it() {
+        let (dir, archive) = write_test_bundle(2, |_| {});
+
+        let inspection = inspect_archive(&archive).unwrap();
+        assert_eq!(inspection.format, "tar.gz");
+        assert!(inspection.warnings.is_empty(), "{:?}", inspection.warnings);
+        let public_parameters = inspection.public_parameters.unwrap();
+        assert_eq!(
+            (public_parameters.nu, public_parameters.vector_length),
+            (2, 4)
+        );
+        let handle = inspection.blitzar_handle.unwrap();
+        assert_eq!((handle.partitions, handle.generators), (1, 16));
+        assert_eq!(inspection.metadata.unwrap().nu, 2);
+        assert!(!inspection.signed);
+
+        // Every member the manifest lists was hashed on the way through the archive
+        let manifest = inspection.manifest.unwrap();
+        for listed in &manifest.members {
+            assert!(inspection.members.contains(listed), "{}", listed.name);
+        }
+
+        // A header that disagrees with the member's size is reported, not trusted
+        let sizes = ArtifactSizes::for_nu(2);
+        let mut bytes = vec![0u8; sizes.public_parameters as usize];
+        bytes[0] = 3;
+        let forged = dir.join("forged.tar");
+        let mut archive = ArchiveWriter::create(
+            &forged,
+            CompressionOptions::new(ArchiveFormat::Tar),
+            &NoProgress,
+        )
+        .unwrap();
+        archive
+            .append(PUBLIC_PARAMETERS_FILE, bytes.len() as u64, &bytes[..])
+            .unwrap();
+        archive.finish().unwrap();
+        let inspection = inspect_archive(&forged).unwrap();
+        assert_eq!(inspection.public_parameters, None);
+        assert_eq!(inspection.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_diff_compares_contents_rather_than_archive_bytes() {
+        let dir = TempDir::new("dory-test").unwrap();
+        let bundle = |public_parameters: &PublicParameters, name: &str, format: ArchiveFormat| {
+            write_bundle_into(dir.path(), public_parameters, &NoProgress, |opts| {
+                opts.archive_name = format!("{}{}", name, format.extension());
+                opts.compression = CompressionOptions::new(format);
+            })
+            .remove(0)
+        };
+        let larger = generate(3, &Seed::default(), &NoProgress).unwrap();
+        let smaller = RawParameters::from_public_parameters(&larger)
+            .unwrap()
+            .truncate(2)
+            .unwrap()
+            .to_public_parameters()
+            .unwrap();
+        let other = generate(3, &Seed::from_string("other").unwrap(), &NoProgress).unwrap();
+        let larger_gz = bundle(&larger, "larger", ArchiveFormat::TarGz);
+        let larger_tar = bundle(&larger, "larger", ArchiveFormat::Tar);
+        let smaller_gz = bundle(&smaller, "smaller", ArchiveFormat::TarGz);
+        let other_gz = bundle(&other, "other", ArchiveFormat::TarGz);
+
+        // The same parameters in different formats are equivalent
+        let diff = diff_bundles(&larger_gz, &larger_tar, &NoProgress).unwrap();
+        assert_eq!(diff.relation, Relation::Equivalent);
+        assert!(diff.handles_agree());
+        assert!(diff.is_equivalent());
+
+        let diff = diff_bundles(&smaller_gz, &larger_gz, &NoProgress).unwrap();
+        assert_eq!((diff.first_nu, diff.second_nu), (2, 3));
+        assert_eq!(diff.relation, Relation::FirstIsPrefix);
+        assert!(diff.handles_agree());
+        let diff = diff_bundles(&larger_gz, &smaller_gz, &NoProgress).unwrap();
+        assert_eq!(diff.relation, Relation::SecondIsPrefix);
+        assert!(!diff.is_equivalent());
+
+        let diff = diff_bundles(&larger_gz, &other_gz, &NoProgress).unwrap();
+        assert_eq!(diff.relation, Relation::Unrelated);
+        assert_eq!(
+            diff.first_difference,
+            Some(ElementDifference {
+                vector: "gamma_1",
+                index: 0,
+                group: "G1",
+            })
+        );
+        assert_eq!(diff.blitzar_handles.first_difference, Some(0));
+        assert!(diff.handles_agree());
+
+        // An archive that cannot be read is named in the error
+        let truncated = dir.join("truncated.tar.gz");
+        let bytes = std::fs::read(&larger_gz).unwrap();
+        std::fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        let error = diff_bundles(&larger_gz, &truncated, &NoProgress).unwrap_err();
+        assert_eq!(error.path, truncated);
+        let missing = dir.join("missing.tar.gz");
+        let error = diff_bundles(&missing, &larger_gz, &NoProgress).unwrap_err();
+        assert_eq!(error.path, missing);
+    }
+
+    #[test]
+    fn test_repeated_runs_write_identical_archives() {
+        let dir = TempDir::new("dory-test").unwrap();
+        for format in ArchiveFormat::ALL {
+            // Two separate runs, compressing on different numbers of threads
+            let runs: Vec<_> = [1, 3]
+                .into_iter()
+                .map(|threads| {
+                    let out_dir = dir.join(&format!("{}-threads{}", format, threads));
+                    let public_parameters = generate(2, &Seed::default(), &NoProgress).unwrap();
+                    write_bundle_into(&out_dir, &public_parameters, &NoProgress, |opts| {
+                        opts.archive_name = format!("dory-params{}", format.extension());
+                        opts.verifier_archive_name = format!("dory-verifier{}", format.extension());
+                        opts.verifier_setup = true;
+                        opts.compression = CompressionOptions {
+                            threads,
+                            // Small enough xz blocks that the handle spans several of them
+                            level: (format == ArchiveFormat::TarXz).then_some(1),
+                            ..CompressionOptions::new(format)
+                        };
+                    })
+                })
+                .collect();
+            assert_eq!(runs[0].len(), 2);
+            for (first, second) in runs[0].iter().zip(&runs[1]) {
+                assert_eq!(
+                    manifest::sha256_file(first).unwrap(),
+                    manifest::sha256_file(second).unwrap()
+                );
+            }
+
+            // Nothing about the run that wrote them is recorded in the member headers
+            let mut archive = open_archive(&runs[0][0]).unwrap();
+            for entry in archive.entries().unwrap() {
+                let header = entry.unwrap().header().clone();
+                assert_eq!(header.uid().unwrap(), 0);
+                assert_eq!(header.gid().unwrap(), 0);
+                assert_eq!(header.mode().unwrap(), 0o644);
+            }
+        }
+    }
 }
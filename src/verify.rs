@@ -0,0 +1,202 @@
+use crate::{
+    archive::{first_difference, unpack_archive, BLITZAR_HANDLE_FILE, PUBLIC_PARAMETERS_FILE},
+    hash_to_curve::GeneratorHasher,
+    manifest::{self, Manifest, MANIFEST_FILE, SIGNATURE_FILE},
+    metadata::{BundleMetadata, METADATA_FILE},
+    progress::Progress,
+    raw_parameters::RawParameters,
+    temp_dir::TempDir,
+};
+use blitzar::compute::MsmHandle;
+use proof_of_sql::proof_primitive::dory::{ProverSetup, PublicParameters};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use std::{fs, path::Path, time::Instant};
+
+/// Check that an archive unpacks and yields a usable `ProverSetup`.
+///
+/// Any manifest in the archive is checked against the unpacked members, and when `public_key`
+/// is given the manifest must carry a signature that verifies against it. When `regenerate` is
+/// set the parameters are regenerated from the `nu` and seed (or hash-to-curve tag) recorded in
+/// the archive's metadata and both artifacts are compared byte-for-byte. `spot_check` recomputes
+/// that many random generators of a hash-to-curve archive, which is far cheaper than regenerating.
+/// Status lines and regeneration progress go to `progress`.
+pub fn verify_archive(
+    archive_path: &Path,
+    regenerate: bool,
+    spot_check: Option<usize>,
+    public_key: Option<&Path>,
+    progress: &dyn Progress,
+) -> Result<(), String> {
+    let work_dir = TempDir::new("dory-verify")
+        .map_err(|e| format!("failed to create a temporary directory: {}", e))?;
+    unpack_archive(archive_path, work_dir.path())
+        .map_err(|e| format!("failed to unpack {}: {}", archive_path.display(), e))?;
+
+    let public_params_path = work_dir.join(PUBLIC_PARAMETERS_FILE);
+    let blitzar_handle_path = work_dir.join(BLITZAR_HANDLE_FILE);
+    for path in [&public_params_path, &blitzar_handle_path] {
+        if !path.is_file() {
+            return Err(format!("archive is missing {}", path.display()));
+        }
+    }
+
+    check_manifest(work_dir.path(), public_key, progress)?;
+
+    // Load the public parameters and rebuild the prover setup from the stored handle
+    let public_parameters = PublicParameters::load_from_file(&public_params_path)
+        .map_err(|e| format!("failed to load {}: {}", PUBLIC_PARAMETERS_FILE, e))?;
+    let blitzar_handle = MsmHandle::new_from_file(&blitzar_handle_path.to_string_lossy());
+    let _prover_setup =
+        ProverSetup::from_public_parameters_and_blitzar_handle(&public_parameters, blitzar_handle);
+    progress.message("Loaded public parameters and rebuilt the prover setup.");
+
+    if !regenerate && spot_check.is_none() {
+        return Ok(());
+    }
+    let metadata_path = work_dir.join(METADATA_FILE);
+    if !metadata_path.is_file() {
+        return Err(format!(
+            "archive has no {}, cannot check how it was generated",
+            METADATA_FILE
+        ));
+    }
+    let metadata = BundleMetadata::load_from_file(&metadata_path)
+        .map_err(|e| format!("failed to read {}: {}", METADATA_FILE, e))?;
+
+    if let Some(samples) = spot_check {
+        let dst = metadata.hash_to_curve_dst.as_deref().ok_or_else(|| {
+            "only archives derived by hash-to-curve can be spot-checked".to_string()
+        })?;
+        let parameters = RawParameters::from_public_parameters(&public_parameters)
+            .map_err(|e| format!("failed to read {}: {}", PUBLIC_PARAMETERS_FILE, e))?;
+        GeneratorHasher::new(dst)?.spot_check(
+            &parameters,
+            samples,
+            &mut ChaCha20Rng::from_entropy(),
+        )?;
+        progress.message(&format!(
+            "{} random generators and the fixed generators match DST {:?}.",
+            samples, dst
+        ));
+    }
+    if !regenerate {
+        return Ok(());
+    }
+
+    // Regenerate from the recorded nu and seed or tag
+    if let Some(source) = metadata.ceremony_source() {
+        return Err(format!(
+            "parameters came from a {}, verify that transcript instead of regenerating",
+            source
+        ));
+    }
+    let start_time = Instant::now();
+    let regenerated = match &metadata.hash_to_curve_dst {
+        Some(dst) => {
+            progress.message(&format!(
+                "Regenerating parameters for nu = {} from DST {:?}...",
+                metadata.nu, dst
+            ));
+            GeneratorHasher::new(dst)?
+                .derive(metadata.nu, progress)
+                .to_public_parameters()
+                .map_err(|e| format!("failed to build regenerated parameters: {}", e))?
+        }
+        None => {
+            let seed = metadata.seed()?;
+            progress.message(&format!(
+                "Regenerating parameters for nu = {} from {}...",
+                metadata.nu,
+                metadata.derivation()
+            ));
+            PublicParameters::rand(metadata.nu, &mut seed.rng())
+        }
+    };
+    let regenerated_params_path = work_dir.join("regenerated_public_parameters.bin");
+    regenerated
+        .save_to_file(&regenerated_params_path)
+        .map_err(|e| format!("failed to save regenerated parameters: {}", e))?;
+    let regenerated_handle_path = work_dir.join("regenerated_blitzar_handle.bin");
+    ProverSetup::from(&regenerated)
+        .blitzar_handle()
+        .write(&regenerated_handle_path.to_string_lossy());
+    progress.message(&format!("Regenerated in {:.2?}", start_time.elapsed()));
+
+    // Compare both artifacts byte-for-byte
+    for (name, stored, fresh) in [
+        (
+            PUBLIC_PARAMETERS_FILE,
+            &public_params_path,
+            &regenerated_params_path,
+        ),
+        (
+            BLITZAR_HANDLE_FILE,
+            &blitzar_handle_path,
+            &regenerated_handle_path,
+        ),
+    ] {
+        match first_difference(stored, fresh)
+            .map_err(|e| format!("failed to compare {}: {}", name, e))?
+        {
+            Some(offset) => {
+                return Err(format!(
+                    "{} differs from the regenerated copy at byte {}",
+                    name, offset
+                ))
+            }
+            None => progress.message(&format!("{} matches the regenerated copy.", name)),
+        }
+    }
+    Ok(())
+}
+
+// Check the manifest and optional signature against the unpacked archive in `dir`
+fn check_manifest(
+    dir: &Path,
+    public_key: Option<&Path>,
+    progress: &dyn Progress,
+) -> Result<(), String> {
+    let manifest_path = dir.join(MANIFEST_FILE);
+    if !manifest_path.is_file() {
+        return match public_key {
+            Some(_) => Err(format!("archive has no {} to verify", MANIFEST_FILE)),
+            None => {
+                progress.message("Archive has no manifest, skipping digest checks.");
+                Ok(())
+            }
+        };
+    }
+    let manifest_json =
+        fs::read(&manifest_path).map_err(|e| format!("failed to read {}: {}", MANIFEST_FILE, e))?;
+
+    if let Some(public_key) = public_key {
+        let signature = fs::read_to_string(dir.join(SIGNATURE_FILE))
+            .map_err(|_| format!("archive has no {}", SIGNATURE_FILE))?;
+        manifest::verify_signature(public_key, &manifest_json, &signature)?;
+        progress.message("Manifest signature is valid.");
+    }
+
+    let manifest: Manifest = serde_json::from_slice(&manifest_json)
+        .map_err(|e| format!("failed to parse {}: {}", MANIFEST_FILE, e))?;
+    manifest.check_members(dir)?;
+
+    // Every other file in the archive must be listed in the manifest
+    let entries = fs::read_dir(dir).map_err(|e| format!("failed to list archive: {}", e))?;
+    for entry in entries {
+        let name = entry
+            .map_err(|e| format!("failed to list archive: {}", e))?
+            .file_name()
+            .to_string_lossy()
+            .into_owned();
+        let listed = manifest.members.iter().any(|m| m.name == name);
+        if !listed && name != MANIFEST_FILE && name != SIGNATURE_FILE {
+            return Err(format!("{} is not listed in the manifest", name));
+        }
+    }
+    progress.message(&format!(
+        "All {} manifest members match their digests.",
+        manifest.members.len()
+    ));
+    Ok(())
+}